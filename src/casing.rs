@@ -0,0 +1,113 @@
+use serde_json::{Map, Value};
+
+/// Field-name casing for JSON responses, configured with `JSON_CASE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonCase {
+    Snake,
+    Camel,
+}
+
+impl JsonCase {
+    pub fn from_config(value: Option<&str>) -> JsonCase {
+        match value {
+            Some("camel") => JsonCase::Camel,
+            _ => JsonCase::Snake,
+        }
+    }
+
+    /// Rewrites object keys in `value` to this casing. Fields are written in
+    /// snake_case, so `Snake` leaves everything alone.
+    pub fn apply(self, value: Value) -> Value {
+        match self {
+            JsonCase::Snake => value,
+            JsonCase::Camel => camelize(value),
+        }
+    }
+}
+
+fn camelize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (camelize_key(key), camelize(value)))
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(camelize).collect()),
+        value => value,
+    }
+}
+
+/// Only keys that look like field names (`pub_date`) are converted. Data keys
+/// such as platform names (`windows-x86_64`) are left intact.
+fn camelize_key(key: String) -> String {
+    let is_field_name = key.contains('_') && key.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+
+    if !is_field_name {
+        return key;
+    }
+
+    let mut camel = String::with_capacity(key.len());
+    let mut upper_next = false;
+
+    for c in key.chars() {
+        if c == '_' {
+            upper_next = true;
+        } else if upper_next {
+            camel.push(c.to_ascii_uppercase());
+            upper_next = false;
+        } else {
+            camel.push(c);
+        }
+    }
+
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn snake_leaves_fields_alone() {
+        let value = json!({ "pub_date": "2024-02-01T00:00:00Z" });
+
+        assert_eq!(JsonCase::from_config(None).apply(value.clone()), value);
+        assert_eq!(JsonCase::from_config(Some("snake")).apply(value.clone()), value);
+    }
+
+    #[test]
+    fn camel_renames_fields() {
+        let value = json!({ "pub_date": "2024-02-01T00:00:00Z", "minimum_system_version": "10.15", "url": "u" });
+
+        assert_eq!(
+            JsonCase::from_config(Some("camel")).apply(value),
+            json!({ "pubDate": "2024-02-01T00:00:00Z", "minimumSystemVersion": "10.15", "url": "u" })
+        );
+    }
+
+    #[test]
+    fn camel_keeps_platform_and_asset_name_keys() {
+        let platforms = json!({ "windows-x86_64": { "signature": "s" }, "darwin-aarch64": { "signature": "s" } });
+        let checksums = json!({ "app_1.1.0_x64-setup.nsis.zip": "ab", "CHANGELOG.md": "cd", "minimums.json": "ef" });
+
+        assert_eq!(JsonCase::Camel.apply(platforms.clone()), platforms);
+        assert_eq!(JsonCase::Camel.apply(checksums.clone()), checksums);
+    }
+
+    #[test]
+    fn camel_recurses_into_objects_and_arrays() {
+        let value = json!({
+            "platforms": { "linux-x86_64": { "minimum_system_version": "22.04" } },
+            "steps": [{ "elapsed_ms": 3 }, { "elapsed_ms": 4 }],
+        });
+
+        assert_eq!(
+            JsonCase::Camel.apply(value),
+            json!({
+                "platforms": { "linux-x86_64": { "minimumSystemVersion": "22.04" } },
+                "steps": [{ "elapsedMs": 3 }, { "elapsedMs": 4 }],
+            })
+        );
+    }
+}
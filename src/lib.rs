@@ -1,128 +1,3155 @@
 use worker::*;
 use serde_json::json;
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
 use reqwest::Client;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::Duration;
 
-#[derive(Deserialize, Debug)]
+mod casing;
+mod logging;
+mod notes;
+mod patterns;
+mod query;
+
+use casing::JsonCase;
+use logging::LogLevel;
+use notes::{clean_markdown, parse_minimums, render_notes_html, NotesStyle};
+use patterns::AssetPatterns;
+use query::{Channel, Installer, NotesFormat, QueryOptions, SignatureFormat};
+
+#[derive(Deserialize, Debug, Clone)]
 struct GitHubRelease {
     tag_name: String,
+    html_url: String,
+    /// `null` on drafts, which are dropped straight after fetching.
+    #[serde(deserialize_with = "null_as_empty")]
     published_at: String,
+    #[serde(deserialize_with = "null_as_empty")]
     body: String,
+    prerelease: bool,
+    draft: bool,
+    author: Option<GitHubAuthor>,
     assets: Vec<GitHubAsset>,
 }
 
-#[derive(Deserialize, Debug)]
+fn null_as_empty<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GitHubAuthor {
+    login: String,
+}
+
+impl GitHubRelease {
+    /// Bad releases are kept on GitHub but marked by starting the body with a
+    /// `> [!WARNING]` admonition whose first line of text is `YANKED`.
+    fn is_yanked(&self) -> bool {
+        let mut lines = self.body.trim_start().lines().map(|line| line.trim());
+
+        match lines.next().and_then(|line| line.strip_prefix('>')) {
+            Some(first) => {
+                let first = first.trim();
+                match first.strip_prefix("[!WARNING]") {
+                    Some("") => lines
+                        .next()
+                        .and_then(|line| line.strip_prefix('>'))
+                        .map_or(false, |line| line.trim().starts_with("YANKED")),
+                    Some(rest) => rest.trim().starts_with("YANKED"),
+                    None => false,
+                }
+            }
+            None => false,
+        }
+    }
+
+    /// The login of whoever published the release. GitHub sends `null` for
+    /// releases whose author account was deleted.
+    fn published_by(&self) -> Option<&str> {
+        self.author.as_ref().map(|author| author.login.as_str())
+    }
+
+    /// The tag as a version string, i.e. without any `refs/tags/` prefix some
+    /// mirrors include.
+    fn version(&self) -> &str {
+        normalize_tag(&self.tag_name)
+    }
+}
+
+fn normalize_tag(tag: &str) -> &str {
+    tag.strip_prefix("refs/tags/").unwrap_or(tag)
+}
+
+/// Parses a date from GitHub or a mirror of it. RFC 3339 is what GitHub sends;
+/// RFC 2822 and offset-less ISO 8601 (taken as UTC) are what mirrors tend to
+/// send instead.
+fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+        return Some(date);
+    }
+
+    ["%Y-%m-%dT%H:%M:%S%.fZ", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|date| date.and_utc().fixed_offset())
+}
+
+/// Parses a tag or client version as semver, accepting a leading `v`.
+fn parse_version(tag: &str) -> Option<semver::Version> {
+    let tag = normalize_tag(tag);
+    semver::Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+#[derive(Deserialize, Debug, Clone)]
 struct GitHubAsset {
+    id: u64,
     name: String,
+    size: u64,
+    download_count: u64,
     browser_download_url: String,
+    /// Bumped when the asset is re-uploaded, e.g. a replaced `.sig`.
+    #[serde(default)]
+    updated_at: String,
+}
+
+/// Every `target`/`arch` pair the updater knows how to serve, keyed the way
+/// Tauri names platforms (`darwin-aarch64`).
+const PLATFORMS: [(&str, &str); 6] = [
+    ("darwin", "x86_64"),
+    ("darwin", "aarch64"),
+    ("linux", "x86_64"),
+    ("linux", "aarch64"),
+    ("windows", "x86_64"),
+    ("windows", "aarch64"),
+];
+
+/// How bundlers spell each arch in asset names, and the arch each spelling
+/// means.
+const ARCH_MARKERS: [(&str, &str); 5] = [
+    ("x86_64", "x86_64"),
+    ("x64", "x86_64"),
+    ("amd64", "x86_64"),
+    ("aarch64", "aarch64"),
+    ("arm64", "aarch64"),
+];
+
+/// Every route `main` registers, listed in 404 responses so API clients can
+/// see what exists.
+const ROUTES: [&str; 15] = [
+    "/:target/:arch/:current_version",
+    "/update/:platform/:current_version",
+    "/sizes",
+    "/release/latest/coverage",
+    "/selftest",
+    "/downloads/top_assets",
+    "/downloads/by_asset",
+    "/channels/:target/:arch",
+    "/latest",
+    "/v2/:current_version",
+    "/health",
+    "/changelog/:from/:to",
+    "/feed.xml",
+    "/checksums/:tag",
+    "/versions",
+];
+
+/// Per-request state handed to every handler through the router.
+struct RequestState {
+    request_id: String,
+    query: QueryOptions,
+    /// The `owner/repo` of the `?product=` asked for, taking the place of
+    /// `GITHUB_REPO`.
+    repo: Option<String>,
+    asset_patterns: AssetPatterns,
+    log_level: LogLevel,
+    notes_style: NotesStyle,
+    json_case: JsonCase,
+    /// `SIGNATURE_TRIM=true`.
+    trim_signature: bool,
+    /// `STRICT_CURRENT_VERSION=true`.
+    strict_current_version: bool,
+    /// Shared by every GitHub request made while handling this request.
+    client: Client,
+    /// GitHub's rate-limit headers from the last live fetch made while
+    /// handling this request, echoed back to the client by `main`.
+    github_rate_limit: Rc<RefCell<Option<GitHubRateLimit>>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct GitHubRateLimit {
+    remaining: String,
+    reset: String,
 }
 
 #[event(fetch)]
 pub async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
-    let router = Router::new();
+    console_error_panic_hook::set_once();
+    let started = Date::now().as_millis();
+    let config = |name: &str| env.var(name).ok().map(|var| var.to_string());
+    let log_level = LogLevel::from_config(config("LOG_LEVEL").as_deref());
 
-    router
-        .get_async("/:target/:arch/:current_version", get_release)
-        .run(req, env)
-        .await
+    let request_id = select_request_id(req.headers().get("X-Request-Id")?, generate_request_id);
+    let req = strip_trailing_slash(req)?;
+    let method = req.method();
+    let path = req.path();
+
+    let github_rate_limit = Rc::new(RefCell::new(None));
+    let origin = req.headers().get("Origin")?;
+    let if_none_match = req.headers().get("If-None-Match")?;
+    let allowed_origins = env.var("ALLOWED_ORIGINS").map(|var| var.to_string()).unwrap_or_else(|_| "*".to_string());
+
+    let mut response = match QueryOptions::from_url(&req.url()?) {
+        // Every route is a GET, so any preflight can be answered up front.
+        _ if method == Method::Options => Response::empty()?.with_status(204),
+        Ok(query) => match (
+            parse_products(config("PRODUCTS").as_deref()),
+            AssetPatterns::from_config(config("ASSET_PATTERNS").as_deref()),
+        ) {
+            (Err(message), _) | (_, Err(message)) => {
+                console_error!("[{}] {}", request_id, message);
+                Response::error(message, 500)?
+            }
+            (Ok(products), Ok(asset_patterns)) => match product_repo(&products, query.product.as_deref()) {
+                Err(message) => Response::error(message, 404)?,
+                Ok(repo) => {
+                    let router = Router::with_data(RequestState {
+                        request_id: request_id.clone(),
+                        query,
+                        repo,
+                        asset_patterns,
+                        log_level,
+                        notes_style: NotesStyle::from_config(config("NOTES_STYLE").as_deref()),
+                        json_case: JsonCase::from_config(config("JSON_CASE").as_deref()),
+                        trim_signature: config("SIGNATURE_TRIM").as_deref() == Some("true"),
+                        strict_current_version: config("STRICT_CURRENT_VERSION").as_deref() == Some("true"),
+                        client: Client::new(),
+                        github_rate_limit: github_rate_limit.clone(),
+                    });
+
+                    router
+                        .get_async("/:target/:arch/:current_version", get_release)
+                        .get_async("/update/:platform/:current_version", get_platform_release)
+                        .get_async("/sizes", get_sizes)
+                        .get_async("/release/latest/coverage", get_coverage)
+                        .get_async("/selftest", get_selftest)
+                        .get_async("/downloads/top_assets", get_top_assets)
+                        .get_async("/downloads/by_asset", get_downloads_by_asset)
+                        .get_async("/channels/:target/:arch", get_channels)
+                        .get_async("/latest", get_latest)
+                        .get_async("/v2/:current_version", get_v2_release)
+                        .get_async("/health", get_health)
+                        .get_async("/changelog/:from/:to", get_changelog)
+                        .get_async("/feed.xml", get_feed)
+                        .get_async("/checksums/:tag", get_checksums)
+                        .get_async("/versions", get_versions)
+                        .or_else_any_method_async("/*path", not_found)
+                        .run(req, env)
+                        .await?
+                }
+            },
+        },
+        Err(message) => Response::error(message, 400)?,
+    };
+
+    // Polling updaters send back the ETag they were given, so an unchanged
+    // response costs them a 304 instead of the whole body.
+    if method == Method::Get && response.status_code() == 200 {
+        if let ResponseBody::Body(body) = response.body() {
+            let etag = body_etag(body);
+            if if_none_match.as_deref().map_or(false, |if_none_match| etag_matches(if_none_match, &etag)) {
+                response = Response::empty()?.with_status(304).with_headers(response.headers().clone());
+            }
+            response.headers_mut().set("ETag", &etag)?;
+        }
+    }
+
+    if log_level >= LogLevel::Info {
+        console_log!(
+            "[{}] {:?} {} -> {} in {}ms",
+            request_id,
+            method,
+            path,
+            response.status_code(),
+            Date::now().as_millis().saturating_sub(started)
+        );
+    }
+
+    response.headers_mut().set("X-Request-Id", &request_id)?;
+    if let Some(rate_limit) = github_rate_limit.borrow().as_ref() {
+        response.headers_mut().set("X-GitHub-RateLimit-Remaining", &rate_limit.remaining)?;
+        response.headers_mut().set("X-GitHub-RateLimit-Reset", &rate_limit.reset)?;
+    }
+    apply_cors(response.headers_mut(), origin.as_deref(), &allowed_origins)?;
+    Ok(response)
+}
+
+/// `PRODUCTS`, a JSON map of product name to `owner/repo`. Invalid JSON is a
+/// deployment mistake, so it fails every request with a 500 like an invalid
+/// `ASSET_PATTERNS`.
+fn parse_products(value: Option<&str>) -> std::result::Result<BTreeMap<String, String>, String> {
+    match value {
+        Some(value) => serde_json::from_str(value)
+            .map_err(|err| format!("PRODUCTS is not a JSON map of product to owner/repo: {}", err)),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/// The repo configured for `product` in `PRODUCTS`. No product means
+/// `GITHUB_REPO`; an unknown one is an error listing the configured names.
+fn product_repo(products: &BTreeMap<String, String>, product: Option<&str>) -> std::result::Result<Option<String>, String> {
+    let product = match product {
+        Some(product) => product,
+        None => return Ok(None),
+    };
+
+    match products.get(product) {
+        Some(repo) => Ok(Some(repo.clone())),
+        None if products.is_empty() => Err(format!("Unknown product: {} (no products are configured)", product)),
+        None => Err(format!(
+            "Unknown product: {}. Configured products: {}",
+            product,
+            products.keys().cloned().collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// The weak `ETag` for a response body.
+fn body_etag(body: &[u8]) -> String {
+    format!("W/\"{}\"", to_hex(&Sha256::digest(body)[..16]))
+}
+
+/// Whether an `If-None-Match` header names `etag`, using the weak comparison
+/// GET requests call for.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Adds CORS headers for `origin`. `allowed_origins` is `ALLOWED_ORIGINS`:
+/// `*` or a comma-separated list of origins, where only a listed origin is
+/// echoed back.
+fn apply_cors(headers: &mut Headers, origin: Option<&str>, allowed_origins: &str) -> Result<()> {
+    let allowed_origins = allowed_origins.trim();
+
+    if allowed_origins == "*" {
+        headers.set("Access-Control-Allow-Origin", "*")?;
+    } else {
+        headers.append("Vary", "Origin")?;
+        match origin {
+            Some(origin) if allowed_origins.split(',').any(|allowed| allowed.trim() == origin) => {
+                headers.set("Access-Control-Allow-Origin", origin)?;
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    headers.set("Access-Control-Allow-Methods", "GET, OPTIONS")?;
+    headers.set("Access-Control-Allow-Headers", "Authorization, Content-Type, X-Request-Id")?;
+    headers.set(
+        "Access-Control-Expose-Headers",
+        "ETag, X-Installer, X-Request-Id, X-GitHub-RateLimit-Remaining, X-GitHub-RateLimit-Reset",
+    )?;
+    Ok(())
+}
+
+/// Routes are registered without a trailing slash; rebuild the request so
+/// `/sizes/` reaches the same handler as `/sizes`. The root path is left as-is.
+fn strip_trailing_slash(req: Request) -> Result<Request> {
+    let mut url = req.url()?;
+    let path = match trimmed_path(url.path()) {
+        Some(path) => path.to_string(),
+        None => return Ok(req),
+    };
+
+    url.set_path(&path);
+
+    let mut init = RequestInit::new();
+    init.with_method(req.method())
+        .with_headers(req.headers().clone())
+        .with_body(req.inner().body().map(wasm_bindgen::JsValue::from));
+
+    Request::new_with_init(url.as_str(), &init)
+}
+
+/// `path` without its trailing slash, or `None` when it has none to drop.
+fn trimmed_path(path: &str) -> Option<&str> {
+    if path.len() <= 1 {
+        return None;
+    }
+
+    path.strip_suffix('/')
+}
+
+/// The client's `X-Request-Id` when it's usable, so their logs and ours line
+/// up; otherwise a fresh one from `generate`.
+fn select_request_id(header: Option<String>, generate: impl FnOnce() -> String) -> String {
+    match header {
+        Some(id) if is_valid_request_id(&id) => id,
+        _ => generate(),
+    }
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_graphic())
+}
+
+/// Random version 4 UUID. There's no `rand` in the Workers runtime, so the
+/// bytes come from the JS `Math.random`.
+fn generate_request_id() -> String {
+    let mut bytes = [0u8; 16];
+    for byte in bytes.iter_mut() {
+        *byte = (js_sys::Math::random() * 256.0) as u8;
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = to_hex(&bytes);
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
 }
 
-async fn get_release(_req: worker::Request, ctx: RouteContext<()>) -> Result<Response> {
+async fn get_release(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
     let target = ctx.param("target").unwrap();
     let arch = ctx.param("arch").unwrap();
     let current_version = ctx.param("current_version").unwrap();
 
-    let client = Client::new();
-    let url = "https://api.github.com/repos/Valink-Solutions/teller/releases";
-    let resp = match client.get(url)
-        .header("User-Agent", "chunkvault-updater")
-        .send()
-        .await {
-        Ok(resp) => resp,
-        Err(_) => return Response::error("Failed to fetch releases", 500),
+    serve_update(&req, &ctx, target, arch, current_version).await
+}
+
+/// Same as `get_release`, but takes Tauri's single `target-arch` token
+/// (`darwin-aarch64`) instead of two path segments.
+async fn get_platform_release(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let platform = ctx.param("platform").unwrap();
+    let current_version = ctx.param("current_version").unwrap();
+
+    let (target, arch) = match split_platform(platform) {
+        Some(platform) => platform,
+        None => return Response::error("Invalid platform, expected target-arch", 400),
+    };
+
+    serve_update(&req, &ctx, target, arch, current_version).await
+}
+
+async fn not_found(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let body = json!({ "error": "not_found", "path": req.path(), "routes": ROUTES });
+    Ok(json_response(&ctx, &body)?.with_status(404))
+}
+
+/// Liveness probe for load balancers and status pages. Makes no outbound
+/// requests, so it stays cheap and says nothing about GitHub.
+async fn get_health(_req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    json_response(&ctx, &json!({ "status": "ok", "time": Utc::now().to_rfc3339() }))
+}
+
+/// Tauri v2 updater payload: one response covering every platform, with each
+/// platform's `url` and `signature` under `platforms`.
+async fn get_v2_release(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let current_version = match parse_current_version(ctx.param("current_version").unwrap()) {
+        Ok(current_version) => current_version,
+        Err(message) => return Response::error(message, 400),
+    };
+
+    let latest_release = match check_for_update(&GitHubSource::new(&ctx), &ctx.data.query, &current_version, ctx.data.strict_current_version).await {
+        Ok(UpdateCheck::Available { latest, .. }) => latest,
+        Ok(UpdateCheck::UpToDate) => return Ok(Response::empty()?.with_status(204)),
+        Ok(UpdateCheck::NoReleases) => return Response::error("No releases found", 404),
+        Ok(UpdateCheck::UnknownVersion) => return Response::error("Unknown current_version", 400),
+        Err(err) => return fetch_error_response(&req, &ctx, err),
     };
+    let latest_release = &latest_release;
 
-    let releases: Vec<GitHubRelease> = match resp.json().await {
+    let pub_date = match parse_date(&latest_release.published_at) {
+        Some(pub_date) => pub_date,
+        None => return Response::error("Failed to parse published date", 500),
+    };
+
+    let minimums = resolve_minimums(&ctx, latest_release).await.unwrap_or_default();
+    let mut platforms = serde_json::Map::new();
+
+    for &(target, arch) in PLATFORMS.iter() {
+        let (update_asset, signature_asset) = match (
+            find_update_asset(&ctx.data.asset_patterns, latest_release, target, arch, false),
+            find_update_asset(&ctx.data.asset_patterns, latest_release, target, arch, true),
+        ) {
+            (Some(update_asset), Some(signature_asset)) => (update_asset, signature_asset),
+            _ => continue,
+        };
+
+        // One unreadable signature shouldn't stop every other platform from
+        // updating, so that platform is left out instead.
+        let signature = match fetch_signature(&ctx, signature_asset).await {
+            Ok(signature) => format_signature(signature, ctx.data.query.sig),
+            Err(message) => {
+                console_error!(
+                    "[{}] leaving {} out of the v2 payload: {}",
+                    ctx.data.request_id,
+                    platform_key(target, arch),
+                    message
+                );
+                continue;
+            }
+        };
+
+        let mut platform = json!({
+            "signature": signature,
+            "url": update_asset.browser_download_url,
+        });
+        if let Some(minimum_system_version) = minimum_for_target(&minimums, target) {
+            platform["minimum_system_version"] = json!(minimum_system_version);
+        }
+
+        platforms.insert(platform_key(target, arch), platform);
+    }
+
+    json_response(
+        &ctx,
+        &json!({
+            "version": latest_release.version(),
+            "notes": clean_markdown(&latest_release.body, ctx.data.notes_style),
+            "pub_date": pub_date.to_rfc3339(),
+            "platforms": platforms,
+        }),
+    )
+}
+
+/// Cleaned notes of every release after `from` up to and including `to`,
+/// newest first, for clients that skipped several versions.
+async fn get_changelog(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let (from, to) = match (
+        ctx.param("from").and_then(|from| parse_version(from)),
+        ctx.param("to").and_then(|to| parse_version(to)),
+    ) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return Response::error("from and to must be semver versions", 400),
+    };
+
+    if from >= to {
+        return json_response(&ctx, &json!([]));
+    }
+
+    let releases = match unyanked_releases(&req, &ctx).await? {
         Ok(releases) => releases,
-        Err(_) => return Response::error("Failed to parse releases", 500),
+        Err(response) => return Ok(response),
     };
 
-    let latest_release = match releases.iter().find(|&release| release.tag_name != current_version.to_owned()) {
-        Some(release) => release,
-        None => return Response::error("No new release found", 404),
+    let changelog: Vec<serde_json::Value> = releases_between(&releases, &from, &to)
+        .into_iter()
+        .map(|release| {
+            json!({
+                "version": release.version(),
+                "pub_date": parse_date(&release.published_at).map_or_else(|| release.published_at.clone(), |date| date.to_rfc3339()),
+                "notes": clean_markdown(&release.body, ctx.data.notes_style),
+            })
+        })
+        .collect();
+
+    json_response(&ctx, &changelog)
+}
+
+/// An Atom feed of the newest releases for feed readers, `?limit=` entries
+/// long (20 by default). Readers poll quietly, so the response may be cached
+/// for five minutes.
+async fn get_feed(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let limit = ctx.data.query.limit.unwrap_or(20);
+
+    let mut releases = match unyanked_releases(&req, &ctx).await? {
+        Ok(releases) => releases,
+        Err(response) => return Ok(response),
     };
+    retain_channel(&ctx.data.query, &mut releases);
 
-    let (file_extension, sig_file_extension) = get_file_extension(&target, &arch);
+    let repo = ctx.data.repo.clone().or_else(|| env_var(&ctx, "GITHUB_REPO")).unwrap_or_default();
+    let feed = atom_feed(&ctx.data.asset_patterns, ctx.data.notes_style, &repo, req.url()?.as_str(), releases, limit);
 
-    if file_extension.is_empty() || sig_file_extension.is_empty() {
-        return Response::error("Invalid target", 400);
+    let mut response = Response::ok(feed)?;
+    response.headers_mut().set("Content-Type", "application/atom+xml; charset=utf-8")?;
+    response.headers_mut().set("Cache-Control", "public, max-age=300")?;
+    Ok(response)
+}
+
+/// The Atom document for `/feed.xml`: the `limit` newest releases, newest
+/// first. Releases without a valid publish date are left out, since Atom
+/// requires every `<updated>` to be a real timestamp.
+fn atom_feed(
+    patterns: &AssetPatterns,
+    notes_style: NotesStyle,
+    repo: &str,
+    feed_url: &str,
+    releases: Vec<GitHubRelease>,
+    limit: usize,
+) -> String {
+    let mut releases: Vec<(DateTime<FixedOffset>, GitHubRelease)> = releases
+        .into_iter()
+        .filter_map(|release| parse_date(&release.published_at).map(|published| (published, release)))
+        .collect();
+    releases.sort_by(|a, b| b.0.cmp(&a.0));
+    releases.truncate(limit);
+
+    let updated = releases.first().map_or_else(|| Utc::now().to_rfc3339(), |(published, _)| published.to_rfc3339());
+
+    let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(feed, "  <title>{} releases</title>", escape_xml(repo));
+    let feed_url = escape_xml(feed_url);
+    let _ = writeln!(feed, "  <id>{}</id>", feed_url);
+    let _ = writeln!(feed, "  <link rel=\"self\" href=\"{}\"/>", feed_url);
+    let _ = writeln!(feed, "  <updated>{}</updated>", updated);
+    let _ = writeln!(feed, "  <author><name>{}</name></author>", escape_xml(repo));
+
+    for (published, release) in &releases {
+        feed.push_str("  <entry>\n");
+        let _ = writeln!(feed, "    <title>{}</title>", escape_xml(&release.tag_name));
+        let _ = writeln!(feed, "    <id>{}</id>", escape_xml(&release.html_url));
+        let _ = writeln!(feed, "    <updated>{}</updated>", published.to_rfc3339());
+        let _ = writeln!(
+            feed,
+            "    <author><name>{}</name></author>",
+            escape_xml(release.published_by().unwrap_or(repo))
+        );
+        let _ = writeln!(feed, "    <link rel=\"alternate\" href=\"{}\"/>", escape_xml(&release.html_url));
+        // The primary download is the first platform with an installer.
+        let primary_asset = PLATFORMS
+            .iter()
+            .find_map(|&(target, arch)| find_update_asset(patterns, release, target, arch, false));
+        if let Some(asset) = primary_asset {
+            let _ = writeln!(
+                feed,
+                "    <link rel=\"enclosure\" href=\"{}\" length=\"{}\"/>",
+                escape_xml(&asset.browser_download_url),
+                asset.size
+            );
+        }
+        let _ = writeln!(
+            feed,
+            "    <content type=\"text\">{}</content>",
+            escape_xml(&clean_markdown(&release.body, notes_style))
+        );
+        feed.push_str("  </entry>\n");
     }
+    feed.push_str("</feed>\n");
 
-    let update_asset = match latest_release.assets.iter().find(|asset| asset.name.ends_with(&file_extension)) {
-        Some(asset) => asset,
-        None => return Response::error("No update asset found", 404),
+    feed
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// SHA-256 of every asset in the release tagged `tag` (or the newest release
+/// for `latest`), keyed by asset name. `?asset=<name>` hashes just that one.
+/// Each asset is downloaded in full, so tagged responses are marked
+/// immutable for caches to hold on to, and requests over the
+/// `checksum_budget` are refused before anything is downloaded.
+async fn get_checksums(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let tag = ctx.param("tag").unwrap();
+
+    let release = if tag == "latest" {
+        match latest_release(&req, &ctx).await? {
+            Ok(release) => release,
+            Err(response) => return Ok(response),
+        }
+    } else {
+        let releases = match GitHubSource::new(&ctx).releases().await {
+            Ok(releases) => releases,
+            Err(err) => return fetch_error_response(&req, &ctx, err),
+        };
+        match releases.into_iter().find(|release| release.tag_name == *tag || release.version() == tag) {
+            Some(release) => release,
+            None => return Response::error("Release not found", 404),
+        }
     };
 
-    let download_url = update_asset.browser_download_url.clone();
-    let new_version = latest_release.tag_name.clone();
+    let assets: Vec<&GitHubAsset> = match &ctx.data.query.asset {
+        Some(name) => match release.assets.iter().find(|asset| asset.name == *name) {
+            Some(asset) => vec![asset],
+            None => return Response::error("Asset not found", 404),
+        },
+        None => release.assets.iter().collect(),
+    };
+    if let Err(message) = checksum_budget(&assets) {
+        return Response::error(message, 400);
+    }
 
-    let pub_date: DateTime<FixedOffset> = match DateTime::parse_from_rfc3339(
-        latest_release.published_at.as_str(),
-    ) {
-        Ok(pub_date) => pub_date,
-        Err(_) => return Response::error("Failed to parse published date", 500),
+    let mut checksums = BTreeMap::new();
+    for asset in assets {
+        match fetch_sha256(&ctx, asset).await {
+            Ok(checksum) => checksums.insert(asset.name.clone(), checksum),
+            Err(message) => return Response::error(message, 500),
+        };
+    }
+
+    let mut response = json_response(&ctx, &checksums)?;
+    if tag != "latest" {
+        response.headers_mut().set("Cache-Control", "public, max-age=31536000, immutable")?;
+    }
+    Ok(response)
+}
+
+/// Most assets one `/checksums` request downloads. Each is a subrequest, and
+/// Workers cap those per request.
+const MAX_CHECKSUM_ASSETS: usize = 20;
+
+/// Most bytes one `/checksums` request downloads. Assets are held in memory
+/// while they're hashed, and a Worker gets 128 MB.
+const MAX_CHECKSUM_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Refuses to hash `assets` when that would take more subrequests or memory
+/// than a Worker can spare, using the sizes GitHub lists.
+fn checksum_budget(assets: &[&GitHubAsset]) -> std::result::Result<(), &'static str> {
+    if assets.len() > MAX_CHECKSUM_ASSETS {
+        return Err("Release has too many assets to checksum at once; pass asset=<name>");
+    }
+    if assets.iter().any(|asset| asset.size > MAX_CHECKSUM_BYTES) {
+        return Err("Asset is too large to checksum");
+    }
+    if assets.iter().map(|asset| asset.size).sum::<u64>() > MAX_CHECKSUM_BYTES {
+        return Err("Assets are too large to checksum at once; pass asset=<name>");
+    }
+
+    Ok(())
+}
+
+/// Every released version, newest first by semver, for changelog UIs and
+/// "older downloads" pages. Prereleases are left out unless
+/// `?include_prereleases=true`; `?limit=` caps the list.
+async fn get_versions(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let mut releases = match unyanked_releases(&req, &ctx).await? {
+        Ok(releases) => releases,
+        Err(response) => return Ok(response),
     };
+    if !ctx.data.query.include_prereleases {
+        releases.retain(|release| !release.prerelease);
+    }
 
-    let notes = latest_release.body.clone();
-    let signature_asset = match latest_release.assets.iter().find(|asset| asset.name.ends_with(&sig_file_extension)) {
-        Some(asset) => asset,
-        None => return Response::error("No signature asset found", 404),
+    let mut versions: Vec<(semver::Version, &GitHubRelease)> = releases
+        .iter()
+        .filter_map(|release| parse_version(&release.tag_name).map(|version| (version, release)))
+        .collect();
+    versions.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let versions: Vec<serde_json::Value> = versions
+        .into_iter()
+        .take(ctx.data.query.limit.unwrap_or(usize::MAX))
+        .map(|(_, release)| {
+            json!({
+                "version": release.version(),
+                "pub_date": parse_date(&release.published_at).map_or_else(|| release.published_at.clone(), |date| date.to_rfc3339()),
+                "prerelease": release.prerelease,
+            })
+        })
+        .collect();
+
+    json_response(&ctx, &versions)
+}
+
+/// The newest release regardless of the caller's version, for badges and
+/// "current version" displays. `?target=&arch=` narrows the assets down to
+/// that platform's download URL.
+async fn get_latest(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let latest_release = match latest_release(&req, &ctx).await? {
+        Ok(release) => release,
+        Err(response) => return Ok(response),
     };
-    let signature_url = signature_asset.browser_download_url.clone();
 
-    let signature_resp = match client.get(signature_url).send().await {
-        Ok(resp) => resp,
-        Err(_) => return Response::error("Failed to fetch signature", 500),
+    let mut response_body = json!({
+        "tag_name": latest_release.tag_name,
+        "published_at": latest_release.published_at,
+    });
+
+    match (&ctx.data.query.target, &ctx.data.query.arch) {
+        (Some(target), Some(arch)) => match find_update_asset(&ctx.data.asset_patterns, &latest_release, target, arch, false) {
+            Some(asset) => response_body["url"] = json!(asset.browser_download_url),
+            None => return missing_asset_response(&ctx, "No asset found for target", &latest_release),
+        },
+        (None, None) => {
+            response_body["assets"] = json!(latest_release
+                .assets
+                .iter()
+                .map(|asset| json!({ "name": asset.name, "url": asset.browser_download_url }))
+                .collect::<Vec<_>>());
+        }
+        _ => return Response::error("target and arch must be given together", 400),
+    }
+
+    with_last_modified(&latest_release, json_response(&ctx, &response_body)?)
+}
+
+/// Installer size in bytes for each platform in the newest release.
+async fn get_sizes(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let latest_release = match latest_release(&req, &ctx).await? {
+        Ok(release) => release,
+        Err(response) => return Ok(response),
     };
 
-    let signature = match signature_resp.text().await {
-        Ok(signature) => signature,
-        Err(_) => return Response::error("Failed to parse signature", 500),
+    json_response(&ctx, &platform_sizes(&ctx.data.asset_patterns, &latest_release))
+}
+
+/// The update bundle size of each platform `release` covers, keyed as
+/// `target-arch`.
+fn platform_sizes(patterns: &AssetPatterns, release: &GitHubRelease) -> BTreeMap<String, u64> {
+    PLATFORMS
+        .iter()
+        .filter_map(|&(target, arch)| {
+            find_update_asset(patterns, release, target, arch, false).map(|asset| (platform_key(target, arch), asset.size))
+        })
+        .collect()
+}
+
+/// Whether each platform has both its installer and signature uploaded in the
+/// newest release, so incomplete uploads are easy to spot.
+async fn get_coverage(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let latest_release = match latest_release(&req, &ctx).await? {
+        Ok(release) => release,
+        Err(response) => return Ok(response),
     };
 
-    let response_body = json!({
-        "version": new_version,
+    json_response(&ctx, &platform_coverage(&ctx.data.asset_patterns, &latest_release))
+}
+
+/// Every platform, keyed as `target-arch`, with whether `release` has both
+/// its update bundle and signature.
+fn platform_coverage(patterns: &AssetPatterns, release: &GitHubRelease) -> BTreeMap<String, bool> {
+    PLATFORMS
+        .iter()
+        .map(|&(target, arch)| {
+            let complete = find_update_asset(patterns, release, target, arch, false).is_some()
+                && find_update_asset(patterns, release, target, arch, true).is_some();
+            (platform_key(target, arch), complete)
+        })
+        .collect()
+}
+
+/// The most downloaded assets across every release, most downloaded first.
+async fn get_top_assets(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let limit = ctx.data.query.limit.unwrap_or(10);
+
+    let releases = match unyanked_releases(&req, &ctx).await? {
+        Ok(releases) => releases,
+        Err(response) => return Ok(response),
+    };
+
+    json_response(&ctx, &top_assets(&releases, limit))
+}
+
+/// The `limit` most downloaded assets as `/downloads/top_assets` lists them.
+fn top_assets(releases: &[GitHubRelease], limit: usize) -> Vec<serde_json::Value> {
+    assets_by_downloads(releases)
+        .into_iter()
+        .take(limit)
+        .map(|(release, asset)| {
+            json!({
+                "tag_name": release.tag_name,
+                "asset_name": asset.name,
+                "download_count": asset.download_count,
+            })
+        })
+        .collect()
+}
+
+/// The newest stable and newest beta (prereleases included) update for one
+/// platform, side by side. A channel with nothing to serve is `null`.
+async fn get_channels(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let target = ctx.param("target").unwrap();
+    let arch = ctx.param("arch").unwrap();
+
+    if get_file_extension(target, arch).0.is_empty() {
+        return Response::error("Invalid target", 400);
+    }
+
+    let releases = match unyanked_releases(&req, &ctx).await? {
+        Ok(releases) => releases,
+        Err(response) => return Ok(response),
+    };
+
+    let mut channels = serde_json::Map::new();
+    for (channel, release) in channel_releases(&ctx.data.asset_patterns, &releases, target, arch) {
+        let payload = match release {
+            Some(release) => match channel_payload(&ctx, release, target, arch).await {
+                Ok(payload) => payload,
+                Err(message) => return Response::error(message, 500),
+            },
+            None => serde_json::Value::Null,
+        };
+        channels.insert(channel.to_string(), payload);
+    }
+
+    json_response(&ctx, &channels)
+}
+
+/// The newest release per channel that has both an update bundle and a
+/// signature for the platform. `stable` skips prereleases; `beta` doesn't.
+fn channel_releases<'a>(
+    patterns: &AssetPatterns,
+    releases: &'a [GitHubRelease],
+    target: &str,
+    arch: &str,
+) -> [(&'static str, Option<&'a GitHubRelease>); 2] {
+    let servable = |release: &GitHubRelease| {
+        find_update_asset(patterns, release, target, arch, false).is_some()
+            && find_update_asset(patterns, release, target, arch, true).is_some()
+            && parse_date(&release.published_at).is_some()
+    };
+
+    [
+        ("stable", select_latest(releases.iter().filter(|release| !release.prerelease)).filter(|release| servable(release))),
+        ("beta", select_latest(releases).filter(|release| servable(release))),
+    ]
+}
+
+/// The update payload for one channel's release. Only releases passed by
+/// `channel_releases` get here, so the only failure is fetching the signature.
+async fn channel_payload(
+    ctx: &RouteContext<RequestState>,
+    release: &GitHubRelease,
+    target: &str,
+    arch: &str,
+) -> std::result::Result<serde_json::Value, &'static str> {
+    let (update_asset, signature_asset) = match (
+        find_update_asset(&ctx.data.asset_patterns, release, target, arch, false),
+        find_update_asset(&ctx.data.asset_patterns, release, target, arch, true),
+    ) {
+        (Some(update_asset), Some(signature_asset)) => (update_asset, signature_asset),
+        _ => return Err("No update asset found"),
+    };
+    let pub_date = match parse_date(&release.published_at) {
+        Some(pub_date) => pub_date,
+        None => return Err("Failed to parse published date"),
+    };
+
+    let signature = fetch_signature(ctx, signature_asset).await?;
+
+    Ok(json!({
+        "version": release.version(),
         "pub_date": pub_date.to_rfc3339(),
-        "url": download_url,
-        "signature": signature,
-        "notes": clean_markdown(&notes)
-    }).to_string();
+        "url": update_asset.browser_download_url,
+        "signature": format_signature(signature, ctx.data.query.sig),
+        "notes": clean_markdown(&release.body, ctx.data.notes_style),
+    }))
+}
+
+/// Download counts per asset, most downloaded first. `?tag=` scopes it to a
+/// single release.
+async fn get_downloads_by_asset(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    let mut releases = match unyanked_releases(&req, &ctx).await? {
+        Ok(releases) => releases,
+        Err(response) => return Ok(response),
+    };
+
+    if let Some(tag) = &ctx.data.query.tag {
+        releases.retain(|release| release.tag_name == *tag || release.version() == tag);
+    }
+
+    let assets: Vec<serde_json::Value> = assets_by_downloads(&releases)
+        .into_iter()
+        .map(|(release, asset)| {
+            json!({
+                "name": asset.name,
+                "download_count": asset.download_count,
+                "tag_name": release.tag_name,
+            })
+        })
+        .collect();
 
-    Ok(Response::from_json(&response_body)?)
+    json_response(&ctx, &assets)
 }
 
-fn get_file_extension(target: &str, _arch: &str) -> (String, String) {
-    match target {
-        "darwin" => (".app.tar.gz".to_string(), ".app.tar.gz.sig".to_string()),
-        "linux" => (".AppImage.tar.gz".to_string(), ".AppImage.tar.gz.sig".to_string()),
-        "windows" => (".nsis.zip".to_string(), ".nsis.zip.sig".to_string()),
-        _ => ("".to_string(), "".to_string()),
+/// Every asset of every release, paired with its release and sorted by
+/// download count, highest first.
+fn assets_by_downloads(releases: &[GitHubRelease]) -> Vec<(&GitHubRelease, &GitHubAsset)> {
+    let mut assets: Vec<(&GitHubRelease, &GitHubAsset)> = releases
+        .iter()
+        .flat_map(|release| release.assets.iter().map(move |asset| (release, asset)))
+        .collect();
+    assets.sort_by(|a, b| b.1.download_count.cmp(&a.1.download_count));
+    assets
+}
+
+/// Post-deploy smoke test: runs the real pipeline against the configured repo
+/// and reports each step, so config mistakes show up right after a deploy.
+async fn get_selftest(req: worker::Request, ctx: RouteContext<RequestState>) -> Result<Response> {
+    if !is_admin(&req, &ctx)? {
+        return Response::error("Unauthorized", 401);
+    }
+
+    let steps = selftest_steps(
+        &GitHubSource::new(&ctx),
+        &ctx.data.query,
+        &ctx.data.asset_patterns,
+        || Date::now().as_millis(),
+        |asset| {
+            let request = asset_request(&ctx, reqwest::Method::HEAD, &asset.browser_download_url);
+            async move {
+                match request.send().await {
+                    Ok(resp) if resp.status().is_success() => None,
+                    Ok(_) => Some("HEAD request returned an error status"),
+                    Err(_) => Some("HEAD request failed"),
+                }
+            }
+        },
+    )
+    .await;
+
+    let ok = steps.iter().all(|step| step["ok"] == true);
+    json_response(&ctx, &json!({ "ok": ok, "steps": steps }))
+}
+
+/// Every `/selftest` step against `source`. `now` is the clock in
+/// milliseconds and `probe` checks one asset is downloadable, returning the
+/// error if not.
+async fn selftest_steps<F: std::future::Future<Output = Option<&'static str>>>(
+    source: &impl ReleaseSource,
+    query: &QueryOptions,
+    patterns: &AssetPatterns,
+    now: impl Fn() -> u64,
+    probe: impl Fn(&GitHubAsset) -> F,
+) -> Vec<serde_json::Value> {
+    let mut steps = Vec::new();
+
+    let started = now();
+    let releases = source.releases().await;
+    steps.push(selftest_step(
+        "fetch_releases",
+        now().saturating_sub(started),
+        releases.as_ref().err().map(|err| err.message.as_ref()),
+    ));
+
+    let mut releases = releases.unwrap_or_default();
+    let started = now();
+    let latest_release = select_latest_for(query, &mut releases);
+    steps.push(selftest_step(
+        "select_latest",
+        now().saturating_sub(started),
+        latest_release.is_none().then_some("No releases found"),
+    ));
+
+    if let Some(latest_release) = latest_release {
+        for &(target, arch) in PLATFORMS.iter() {
+            for (kind, signature) in [("asset", false), ("signature", true)] {
+                let name = format!("{}:{}", kind, platform_key(target, arch));
+                let started = now();
+
+                let error = match find_update_asset(patterns, latest_release, target, arch, signature) {
+                    Some(asset) => probe(asset).await,
+                    None => Some("No matching asset"),
+                };
+
+                steps.push(selftest_step(&name, now().saturating_sub(started), error));
+            }
+        }
     }
+
+    steps
+}
+
+fn selftest_step(name: &str, elapsed_ms: u64, error: Option<&str>) -> serde_json::Value {
+    json!({
+        "name": name,
+        "ok": error.is_none(),
+        "error": error,
+        "elapsed_ms": elapsed_ms,
+    })
 }
 
-fn clean_markdown(markdown: &str) -> String {
-    let header_re = regex::Regex::new(r"(?m)^#+.*\n?").unwrap();
-    let bold_re = regex::Regex::new(r"\*\*.*?\*\*").unwrap();
-    let italic_re = regex::Regex::new(r"_.*?_").unwrap();
-    let link_re = regex::Regex::new(r"\[.*?\]\(.*?\)").unwrap();
-    let specific_text_re = regex::Regex::new(r"\*\*_See the assets to download and install this version\._\*\*").unwrap();
+async fn serve_update(
+    req: &worker::Request,
+    ctx: &RouteContext<RequestState>,
+    target: &str,
+    arch: &str,
+    current_version: &str,
+) -> Result<Response> {
+    if target == "web" {
+        return match web_update(env_var(ctx, "WEB_APP_URL")) {
+            Some(payload) => json_response(ctx, &payload),
+            None => Ok(Response::empty()?.with_status(204)),
+        };
+    }
+
+    let current_version = match parse_current_version(current_version) {
+        Ok(current_version) => current_version,
+        Err(message) => return Response::error(message, 400),
+    };
+    let query = &ctx.data.query;
+
+    let (releases, latest_release) = match check_for_update(&GitHubSource::new(ctx), query, &current_version, ctx.data.strict_current_version).await {
+        Ok(UpdateCheck::Available { releases, latest }) => (releases, latest),
+        Ok(UpdateCheck::UpToDate) => return Ok(Response::empty()?.with_status(204)),
+        Ok(UpdateCheck::NoReleases) => return Response::error("No releases found", 404),
+        Ok(UpdateCheck::UnknownVersion) => return Response::error("Unknown current_version", 400),
+        Err(err) => return fetch_error_response(req, ctx, err),
+    };
+    let latest_release = &latest_release;
+    if ctx.data.log_level >= LogLevel::Debug {
+        console_debug!(
+            "[{}] update check for {} on {}: newest release is {}",
+            ctx.data.request_id,
+            platform_key(target, arch),
+            current_version,
+            latest_release.tag_name
+        );
+    }
+
+    // Discovery only: which platforms the release covers, without resolving
+    // (or fetching) any signatures.
+    if query.platforms_only {
+        let platforms = platforms_with_assets(&ctx.data.asset_patterns, latest_release);
+        return json_response(ctx, &json!({ "version": latest_release.version(), "platforms": platforms }));
+    }
+
+    let (file_extension, sig_file_extension) = get_file_extension(target, arch);
+
+    if file_extension.is_empty() || sig_file_extension.is_empty() {
+        return Response::error("Invalid target", 400);
+    }
+
+    if let Some(message) = unsupported_installer(target, query.installer) {
+        return Response::error(message, 501);
+    }
+
+    let fallback_arch = fallback_arch(ctx, target);
+    let requested_asset = find_update_asset_or_fallback(
+        &ctx.data.asset_patterns,
+        latest_release,
+        target,
+        arch,
+        query.installer,
+        fallback_arch.as_deref(),
+    );
+    let (update_asset, installer, served_arch) = match requested_asset {
+        Some(found) => found,
+        None => return missing_asset_response(ctx, "No update asset found", latest_release),
+    };
+
+    let download_url = update_asset.browser_download_url.clone();
+    let new_version = latest_release.version().to_string();
+
+    let pub_date = match parse_date(&latest_release.published_at) {
+        Some(pub_date) => pub_date,
+        None => return Response::error("Failed to parse published date", 500),
+    };
 
-    let no_headers = header_re.replace_all(markdown, "");
-    let no_bold = bold_re.replace_all(&no_headers, "");
-    let no_italic = italic_re.replace_all(&no_bold, "");
-    let no_links = link_re.replace_all(&no_italic, "");
-    let cleaned_text = specific_text_re.replace_all(&no_links, "");
+    let notes = match env_var(ctx, "NOTES_FROM_ASSET") {
+        Some(asset_name) => fetch_text_asset(ctx, latest_release, &asset_name)
+            .await
+            .unwrap_or_else(|| latest_release.body.clone()),
+        None => latest_release.body.clone(),
+    };
+    let signature_asset = match installer {
+        Some(installer) => find_installer_asset(&ctx.data.asset_patterns, latest_release, served_arch, installer, true),
+        None => find_update_asset(&ctx.data.asset_patterns, latest_release, target, served_arch, true),
+    };
+    let signature_asset = match signature_asset {
+        Some(asset) => asset,
+        None => return missing_asset_response(ctx, "No signature asset found", latest_release),
+    };
 
-    cleaned_text.to_string()
-}
\ No newline at end of file
+    let signature = match fetch_signature(ctx, signature_asset).await {
+        Ok(signature) => signature,
+        Err(message) => return Response::error(message, 500),
+    };
+
+    let signature = format_signature(signature, query.sig);
+
+    let minimum_system_version = resolve_minimums(ctx, latest_release).await.and_then(|minimums| {
+        minimum_for_target(&minimums, target).map(str::to_string)
+    });
+
+    let mut response_body = json!({
+        "version": new_version,
+        "pub_date": pub_date.to_rfc3339(),
+        "url": download_url,
+        "signature": signature,
+        "notes": match &query.notes_since {
+            Some(since) => collect_notes_since(&releases, since, latest_release, ctx.data.notes_style),
+            None => clean_markdown(&notes, ctx.data.notes_style),
+        }
+    });
+
+    if let Some(minimum_system_version) = minimum_system_version {
+        response_body["minimum_system_version"] = json!(minimum_system_version);
+    }
+
+    if served_arch != arch {
+        response_body["arch_fallback"] = json!(true);
+    }
+
+    if query.notes == NotesFormat::Html {
+        response_body["notes_html"] = json!(render_notes_html(&notes));
+    }
+
+    if query.with_author {
+        response_body["published_by"] = json!(latest_release.published_by());
+    }
+
+    if query.with_alternates {
+        response_body["alternates"] = json!(get_alternate_urls(&ctx.data.asset_patterns, latest_release, target, arch));
+    }
+
+    let mut response = json_response(ctx, &response_body)?;
+    response.headers_mut().set("Link", &link_header(latest_release))?;
+    if let Some(installer) = installer {
+        response.headers_mut().set("X-Installer", installer.as_str())?;
+    }
+    with_last_modified(latest_release, response)
+}
+
+/// `Link` header pointing clients at the release's GitHub page.
+fn link_header(release: &GitHubRelease) -> String {
+    format!("<{}>; rel=\"alternate\"", release.html_url)
+}
+
+/// The web build has nothing to install, so its update check points it at the
+/// hosted app instead of failing as an unknown target. `None` (no
+/// `WEB_APP_URL`) is answered with a 204.
+fn web_update(web_app_url: Option<String>) -> Option<serde_json::Value> {
+    web_app_url.map(|url| json!({ "url": url }))
+}
+
+/// What an update check decided for the client's `current_version`.
+enum UpdateCheck {
+    /// `STRICT_CURRENT_VERSION` is on and the version was never released.
+    UnknownVersion,
+    NoReleases,
+    /// Tauri's updater reads 204 No Content as "already up to date".
+    UpToDate,
+    /// `latest` is the release to serve; `releases` are every candidate left
+    /// after filtering, for notes spanning several versions.
+    Available {
+        releases: Vec<GitHubRelease>,
+        latest: GitHubRelease,
+    },
+}
+
+/// Fetches releases from `source`, drops the ones the query doesn't want and
+/// decides whether the client should update. `force` is for repair/reinstall
+/// flows: the newest release is served even if the client is already on it.
+async fn check_for_update(
+    source: &impl ReleaseSource,
+    query: &QueryOptions,
+    current_version: &semver::Version,
+    strict: bool,
+) -> std::result::Result<UpdateCheck, FetchError> {
+    let mut releases = source.releases().await?;
+
+    // A version we never released is usually a local dev build; strict
+    // deployments would rather reject it than offer it the newest release.
+    if strict && !is_released_version(&releases, current_version) {
+        return Ok(UpdateCheck::UnknownVersion);
+    }
+
+    let latest = match select_latest_for(query, &mut releases) {
+        Some(release) => release.clone(),
+        None => return Ok(UpdateCheck::NoReleases),
+    };
+
+    if !query.force && !is_upgrade(&latest, current_version) {
+        return Ok(UpdateCheck::UpToDate);
+    }
+
+    Ok(UpdateCheck::Available { releases, latest })
+}
+
+/// The `target-arch` keys of every platform `release` has an update bundle for.
+fn platforms_with_assets(patterns: &AssetPatterns, release: &GitHubRelease) -> Vec<String> {
+    PLATFORMS
+        .iter()
+        .filter(|&&(target, arch)| find_update_asset(patterns, release, target, arch, false).is_some())
+        .map(|&(target, arch)| platform_key(target, arch))
+        .collect()
+}
+
+/// Whether any release - yanked or prerelease included - is tagged `version`.
+fn is_released_version(releases: &[GitHubRelease], version: &semver::Version) -> bool {
+    releases.iter().any(|release| parse_version(&release.tag_name).as_ref() == Some(version))
+}
+
+/// A 404 naming the release and every asset it does have, so a caller can
+/// spot a naming mismatch instead of guessing.
+fn missing_asset_response(ctx: &RouteContext<RequestState>, message: &str, release: &GitHubRelease) -> Result<Response> {
+    let body = json!({
+        "error": message,
+        "tag_name": release.tag_name,
+        "assets": release.assets.iter().map(|asset| &asset.name).collect::<Vec<_>>(),
+    });
+    Ok(json_response(ctx, &body)?.with_status(404))
+}
+
+/// Stamps `response` with `Last-Modified` for the served release.
+/// `If-Modified-Since` is deliberately never answered with a 304: the served
+/// release can move back in time (a yank rolls clients back to an older
+/// release), so only the body `ETag` decides whether anything changed.
+fn with_last_modified(release: &GitHubRelease, mut response: Response) -> Result<Response> {
+    if let Some(modified) = last_modified(release) {
+        response.headers_mut().set("Last-Modified", &http_date(modified))?;
+    }
+    Ok(response)
+}
+
+/// When `release` last changed: its publish date or, if later, the newest
+/// asset upload, so a re-uploaded `.sig` moves it forward.
+fn last_modified(release: &GitHubRelease) -> Option<DateTime<Utc>> {
+    std::iter::once(&release.published_at)
+        .chain(release.assets.iter().map(|asset| &asset.updated_at))
+        .filter_map(|date| parse_date(date))
+        .max()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// `Sun, 06 Nov 1994 08:49:37 GMT`, as HTTP headers want dates.
+fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[derive(Debug)]
+struct FetchError {
+    message: Cow<'static, str>,
+    status: u16,
+    /// Start of the upstream body when it was an error or failed to parse,
+    /// for `verbose_error`.
+    body_snippet: Option<String>,
+}
+
+impl FetchError {
+    fn new(message: &'static str) -> FetchError {
+        FetchError { message: Cow::Borrowed(message), status: 500, body_snippet: None }
+    }
+}
+
+/// How much of an unparseable upstream body `verbose_error` shows.
+const ERROR_SNIPPET_BYTES: usize = 512;
+
+/// Turns a failed fetch into its error response. Admins can pass `?verbose_error=true` to
+/// see the start of what GitHub actually returned.
+fn fetch_error_response(
+    req: &worker::Request,
+    ctx: &RouteContext<RequestState>,
+    err: FetchError,
+) -> Result<Response> {
+    console_error!("[{}] {}", ctx.data.request_id, err.message);
+
+    match err.body_snippet {
+        Some(snippet) if ctx.data.query.verbose_error && is_admin(req, ctx)? => {
+            Response::error(format!("{}: {}", err.message, redact_tokens(&snippet)), err.status)
+        }
+        _ => Response::error(err.message, err.status),
+    }
+}
+
+fn redact_tokens(text: &str) -> String {
+    let token_re = regex::Regex::new(
+        r"gh[pousr]_[A-Za-z0-9]{16,}|github_pat_[A-Za-z0-9_]{16,}|(?i:bearer|token)\s+[A-Za-z0-9._~+/=-]{8,}",
+    )
+    .unwrap();
+
+    token_re.replace_all(text, "[REDACTED]").to_string()
+}
+
+fn truncate_to_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Remaining GitHub API requests below which every fetch logs a warning.
+const LOW_RATE_LIMIT: u32 = 10;
+
+/// Optional `GITHUB_TOKEN` secret. Authenticated requests get 5000/hour
+/// instead of the 60/hour shared by every Worker on the same outbound IP.
+fn github_token(ctx: &RouteContext<RequestState>) -> Option<String> {
+    ctx.secret("GITHUB_TOKEN")
+        .ok()
+        .map(|token| token.to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// GitHub rejects requests without a User-Agent. `USER_AGENT` names the
+/// deploying app; otherwise the crate name and version are sent.
+fn user_agent(ctx: &RouteContext<RequestState>) -> String {
+    env_var(ctx, "USER_AGENT").unwrap_or_else(|| concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string())
+}
+
+/// Builds a request for a release asset. The token only goes along when
+/// `GITHUB_PRIVATE_REPO=true`, and then only to GitHub itself over https;
+/// public assets don't need it, and mirrors shouldn't see it.
+fn asset_request(
+    ctx: &RouteContext<RequestState>,
+    method: reqwest::Method,
+    url: &str,
+) -> reqwest::RequestBuilder {
+    let request = ctx.data.client.request(method, url).header("User-Agent", user_agent(ctx));
+
+    match github_token(ctx) {
+        Some(token)
+            if env_var(ctx, "GITHUB_PRIVATE_REPO").as_deref() == Some("true")
+                && is_token_host(url, env_var(ctx, "GITHUB_API_HOST").as_deref()) =>
+        {
+            request.bearer_auth(token)
+        }
+        _ => request,
+    }
+}
+
+/// The releases API URL from `GITHUB_REPO`, which is either `owner/repo` or a
+/// full API URL (for mirrors and GitHub Enterprise).
+fn releases_url(ctx: &RouteContext<RequestState>) -> Option<String> {
+    let repo = ctx.data.repo.clone().or_else(|| env_var(ctx, "GITHUB_REPO"))?;
+    let repo = repo.trim().trim_end_matches('/');
+
+    if repo.is_empty() {
+        None
+    } else if repo.starts_with("https://") || repo.starts_with("http://") {
+        Some(repo.to_string())
+    } else {
+        Some(format!("https://api.github.com/repos/{}/releases", repo))
+    }
+}
+
+/// Whether `GITHUB_TOKEN` may be sent to `url`: only over https, to
+/// `api.github.com`, `github.com` (where `browser_download_url` points) or the
+/// GitHub Enterprise host named by `GITHUB_API_HOST`. A plain-http mirror, or
+/// a `Link` header or asset URL pointing somewhere else, never sees it.
+fn is_token_host(url: &str, api_host: Option<&str>) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    let host = match url.host_str() {
+        Some(host) => host,
+        None => return false,
+    };
+
+    url.scheme() == "https"
+        && (host == "api.github.com" || host == "github.com" || api_host.map_or(false, |api_host| host.eq_ignore_ascii_case(api_host.trim())))
+}
+
+/// Pages fetched per listing when `GITHUB_MAX_PAGES` is unset. At 100
+/// releases a page that's 1000 releases.
+const DEFAULT_MAX_PAGES: usize = 10;
+
+/// Where handlers get releases from. `GitHubSource` reads the configured
+/// repo; tests substitute fixtures.
+trait ReleaseSource {
+    async fn releases(&self) -> std::result::Result<Vec<GitHubRelease>, FetchError>;
+}
+
+struct GitHubSource<'a> {
+    ctx: &'a RouteContext<RequestState>,
+}
+
+impl<'a> GitHubSource<'a> {
+    fn new(ctx: &'a RouteContext<RequestState>) -> GitHubSource<'a> {
+        GitHubSource { ctx }
+    }
+}
+
+impl ReleaseSource for GitHubSource<'_> {
+    async fn releases(&self) -> std::result::Result<Vec<GitHubRelease>, FetchError> {
+        fetch_releases(self.ctx).await
+    }
+}
+
+/// Fetches every release, following GitHub's `Link: rel="next"` pagination
+/// for up to `GITHUB_MAX_PAGES` pages so older releases still count towards
+/// totals and version selection.
+async fn fetch_releases(
+    ctx: &RouteContext<RequestState>,
+) -> std::result::Result<Vec<GitHubRelease>, FetchError> {
+    let url = match releases_url(ctx) {
+        Some(url) => url,
+        None => return Err(FetchError::new("GITHUB_REPO is not configured")),
+    };
+    let mut url = match Url::parse(&url) {
+        Ok(url) => url,
+        Err(_) => return Err(FetchError::new("GITHUB_REPO is not a valid URL")),
+    };
+    if !url.query_pairs().any(|(key, _)| key == "per_page") {
+        url.query_pairs_mut().append_pair("per_page", "100");
+    }
+
+    let max_pages = env_var(ctx, "GITHUB_MAX_PAGES")
+        .and_then(|pages| pages.parse::<usize>().ok())
+        .filter(|&pages| pages > 0)
+        .unwrap_or(DEFAULT_MAX_PAGES);
+
+    let mut releases = Vec::new();
+    let mut next_url = Some(url.to_string());
+
+    for _ in 0..max_pages {
+        let url = match next_url.take() {
+            Some(url) => url,
+            None => break,
+        };
+
+        let (page, next) = fetch_releases_page(ctx, &url).await?;
+        releases.extend(page);
+        next_url = next;
+    }
+
+    Ok(releases)
+}
+
+/// Fetches one page of releases, returning it with the next page's URL.
+async fn fetch_releases_page(
+    ctx: &RouteContext<RequestState>,
+    url: &str,
+) -> std::result::Result<(Vec<GitHubRelease>, Option<String>), FetchError> {
+    let request = releases_request(
+        &ctx.data.client,
+        url,
+        &user_agent(ctx),
+        github_token(ctx).as_deref(),
+        env_var(ctx, "GITHUB_API_HOST").as_deref(),
+    );
+
+    let resp = match send_with_retry(ctx, request).await {
+        Ok(resp) => resp,
+        Err(err) => {
+            console_error!("[{}] fetching releases failed: {}", ctx.data.request_id, err);
+            return Err(FetchError::new("Failed to fetch releases"));
+        }
+    };
+
+    if let Some(rate_limit) = github_rate_limit(resp.headers()) {
+        if ctx.data.log_level >= LogLevel::Warn
+            && rate_limit.remaining.parse::<u32>().map_or(false, |remaining| remaining < LOW_RATE_LIMIT)
+        {
+            console_warn!(
+                "[{}] GitHub rate limit is low: {} requests left until {}",
+                ctx.data.request_id,
+                rate_limit.remaining,
+                rate_limit.reset
+            );
+        }
+        *ctx.data.github_rate_limit.borrow_mut() = Some(rate_limit);
+    }
+    if is_rate_limited(&resp) {
+        return Err(FetchError { status: 503, ..FetchError::new("GitHub rate limit exceeded") });
+    }
+    let status = resp.status();
+    let next_url = resp.headers().get("Link").and_then(|value| value.to_str().ok()).and_then(parse_next_link);
+
+    let body = match resp.text().await {
+        Ok(body) => body,
+        Err(_) => return Err(FetchError::new("Failed to read releases")),
+    };
+    let body_snippet = || Some(truncate_to_char_boundary(&body, ERROR_SNIPPET_BYTES).to_string());
+
+    // A bad token (401), a repo the token can't see (403) or a typo in
+    // GITHUB_REPO (404) come back as GitHub's JSON error, not releases.
+    if !status.is_success() {
+        return Err(FetchError {
+            message: Cow::Owned(upstream_error_message(status.as_u16())),
+            status: 502,
+            body_snippet: body_snippet(),
+        });
+    }
+
+    match parse_releases(&body) {
+        Ok(releases) => Ok((releases, next_url)),
+        Err(_) => Err(FetchError { body_snippet: body_snippet(), ..FetchError::new("Failed to parse releases") }),
+    }
+}
+
+/// Names the status GitHub answered a releases request with, and the usual
+/// cause of it.
+fn upstream_error_message(status: u16) -> String {
+    let hint = match status {
+        401 => " (check GITHUB_TOKEN)",
+        403 => " (GITHUB_TOKEN has no access to the repo)",
+        404 => " (check GITHUB_REPO or PRODUCTS)",
+        _ => "",
+    };
+
+    format!("GitHub returned {} for the releases{}", status, hint)
+}
+
+/// The GET for one page of releases, with the token only for `is_token_host`.
+fn releases_request(
+    client: &Client,
+    url: &str,
+    user_agent: &str,
+    token: Option<&str>,
+    api_host: Option<&str>,
+) -> reqwest::RequestBuilder {
+    // The runtime's `fetch`, which reqwest's wasm client sits on, decodes
+    // gzip by itself; identity is asked for anyway so a mirror that
+    // compresses without saying so can't hand back a body that won't parse.
+    let request = client.get(url)
+        .header("User-Agent", user_agent)
+        .header("Accept-Encoding", "identity");
+
+    match token {
+        Some(token) if is_token_host(url, api_host) => request.bearer_auth(token),
+        _ => request,
+    }
+}
+
+/// Waits before each retry of a GitHub request. Three retries add at most
+/// 1.4s, well inside a Worker's time limit.
+const RETRY_BACKOFF_MS: [u64; 3] = [100, 400, 900];
+
+/// Sends `request`, retrying network errors and 5xx responses with backoff.
+/// Any other response is returned as-is - including a rate limit, which
+/// retrying only makes worse.
+async fn send_with_retry(
+    ctx: &RouteContext<RequestState>,
+    request: reqwest::RequestBuilder,
+) -> reqwest::Result<reqwest::Response> {
+    for backoff_ms in RETRY_BACKOFF_MS {
+        let attempt = match request.try_clone() {
+            Some(attempt) => attempt,
+            None => break,
+        };
+
+        match send_logged(ctx, attempt).await {
+            Ok(resp) if !resp.status().is_server_error() => return Ok(resp),
+            Ok(resp) if ctx.data.log_level >= LogLevel::Warn => {
+                console_warn!("[{}] GitHub returned {}, retrying in {}ms", ctx.data.request_id, resp.status(), backoff_ms)
+            }
+            Err(err) if ctx.data.log_level >= LogLevel::Warn => {
+                console_warn!("[{}] GitHub request failed ({}), retrying in {}ms", ctx.data.request_id, err, backoff_ms)
+            }
+            _ => {}
+        }
+        Delay::from(Duration::from_millis(backoff_ms)).await;
+    }
+
+    send_logged(ctx, request).await
+}
+
+/// Sends `request`, logging its upstream status and timing at `debug`.
+async fn send_logged(
+    ctx: &RouteContext<RequestState>,
+    request: reqwest::RequestBuilder,
+) -> reqwest::Result<reqwest::Response> {
+    let started = Date::now().as_millis();
+    let result = request.send().await;
+
+    if ctx.data.log_level >= LogLevel::Debug {
+        let elapsed = Date::now().as_millis().saturating_sub(started);
+        match &result {
+            Ok(resp) => console_debug!("[{}] upstream {} -> {} in {}ms", ctx.data.request_id, resp.url(), resp.status(), elapsed),
+            Err(err) => console_debug!("[{}] upstream request failed in {}ms: {}", ctx.data.request_id, elapsed, err),
+        }
+    }
+
+    result
+}
+
+/// GitHub's `X-RateLimit-Remaining` and `X-RateLimit-Reset`, when it sent both.
+fn github_rate_limit(headers: &reqwest::header::HeaderMap) -> Option<GitHubRateLimit> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+
+    Some(GitHubRateLimit { remaining: header("X-RateLimit-Remaining")?, reset: header("X-RateLimit-Reset")? })
+}
+
+/// GitHub answers an exhausted rate limit with 403 (or 429 for secondary
+/// limits).
+fn is_rate_limited(resp: &reqwest::Response) -> bool {
+    let headers = resp.headers();
+    let remaining = headers.get("X-RateLimit-Remaining").and_then(|value| value.to_str().ok());
+
+    is_rate_limit_status(resp.status().as_u16(), remaining, headers.contains_key("Retry-After"))
+}
+
+/// A 403 is only a rate limit when GitHub says so with `X-RateLimit-Remaining: 0`
+/// or a `Retry-After`; otherwise it's a permissions problem that waiting won't fix.
+fn is_rate_limit_status(status: u16, remaining: Option<&str>, retry_after: bool) -> bool {
+    match status {
+        429 => true,
+        403 => retry_after || remaining.map(str::trim) == Some("0"),
+        _ => false,
+    }
+}
+
+/// Parses one page of GitHub's releases JSON. Drafts are only listed to
+/// tokens with push access and have no public assets, so they're dropped
+/// here and never seen by the handlers.
+fn parse_releases(body: &str) -> serde_json::Result<Vec<GitHubRelease>> {
+    let mut releases: Vec<GitHubRelease> = serde_json::from_str(body)?;
+    releases.retain(|release| !release.draft);
+    Ok(releases)
+}
+
+/// The `rel="next"` URL from a GitHub `Link` header.
+fn parse_next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        let is_next = params.split(';').any(|param| param.trim() == "rel=\"next\"");
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+
+        is_next.then(|| url.to_string())
+    })
+}
+
+/// Drops yanked releases unless the request asks for them with
+/// `?include_yanked=true`.
+/// The releases for this request with yanked ones dropped, or the error
+/// response to send when GitHub couldn't be read.
+async fn unyanked_releases(
+    req: &worker::Request,
+    ctx: &RouteContext<RequestState>,
+) -> Result<std::result::Result<Vec<GitHubRelease>, Response>> {
+    let mut releases = match GitHubSource::new(ctx).releases().await {
+        Ok(releases) => releases,
+        Err(err) => return fetch_error_response(req, ctx, err).map(Err),
+    };
+    retain_unyanked(&ctx.data.query, &mut releases);
+
+    Ok(Ok(releases))
+}
+
+/// The release every "newest release" route reports, picked as the updater
+/// would, or the error response to send instead.
+async fn latest_release(
+    req: &worker::Request,
+    ctx: &RouteContext<RequestState>,
+) -> Result<std::result::Result<GitHubRelease, Response>> {
+    let mut releases = match GitHubSource::new(ctx).releases().await {
+        Ok(releases) => releases,
+        Err(err) => return fetch_error_response(req, ctx, err).map(Err),
+    };
+
+    match select_latest_for(&ctx.data.query, &mut releases) {
+        Some(release) => Ok(Ok(release.clone())),
+        None => Response::error("No releases found", 404).map(Err),
+    }
+}
+
+fn retain_unyanked(query: &QueryOptions, releases: &mut Vec<GitHubRelease>) {
+    if !query.include_yanked {
+        releases.retain(|release| !release.is_yanked());
+    }
+}
+
+/// Drops prereleases from an update check unless the request opts into them
+/// with `?channel=beta`.
+fn retain_channel(query: &QueryOptions, releases: &mut Vec<GitHubRelease>) {
+    if query.channel == Channel::Stable {
+        releases.retain(|release| !release.prerelease);
+    }
+}
+
+/// The newest release by semver, with `published_at` breaking ties. Tags that
+/// don't parse as semver are skipped rather than ranked as 0.0.0, and
+/// GitHub's ordering is not relied on.
+fn select_latest<'a>(releases: impl IntoIterator<Item = &'a GitHubRelease>) -> Option<&'a GitHubRelease> {
+    releases
+        .into_iter()
+        .filter_map(|release| parse_version(&release.tag_name).map(|version| (version, release)))
+        .max_by(|(a_version, a), (b_version, b)| {
+            a_version
+                .cmp(b_version)
+                .then_with(|| parse_date(&a.published_at).cmp(&parse_date(&b.published_at)))
+        })
+        .map(|(_, release)| release)
+}
+
+/// Drops the releases `query` doesn't want (yanked ones, and prereleases off
+/// the beta channel) and picks the newest of the rest, so every "latest"
+/// means the same release the updater would serve.
+fn select_latest_for<'a>(query: &QueryOptions, releases: &'a mut Vec<GitHubRelease>) -> Option<&'a GitHubRelease> {
+    retain_unyanked(query, releases);
+    retain_channel(query, releases);
+    select_latest(releases.iter())
+}
+
+/// Whether `release` is an upgrade over the client's `current_version`.
+fn is_upgrade(release: &GitHubRelease, current_version: &semver::Version) -> bool {
+    parse_version(&release.tag_name).map_or(false, |latest| latest > *current_version)
+}
+
+/// The `current_version` path segment as semver, with an optional leading
+/// `v`. Anything else is rejected rather than compared as a string.
+fn parse_current_version(current_version: &str) -> std::result::Result<semver::Version, String> {
+    parse_version(current_version)
+        .ok_or_else(|| format!("Invalid current_version: {} is not a semver version (e.g. 1.2.3)", current_version))
+}
+
+/// Cleaned notes of every release newer than `since` up to `latest`, newest
+/// first, each under its version as a header line.
+fn collect_notes_since(
+    releases: &[GitHubRelease],
+    since: &semver::Version,
+    latest: &GitHubRelease,
+    style: NotesStyle,
+) -> String {
+    let latest_version = match parse_version(&latest.tag_name) {
+        Some(version) => version,
+        None => return clean_markdown(&latest.body, style),
+    };
+
+    releases_between(releases, since, &latest_version)
+        .iter()
+        .map(|release| format!("{}\n{}", release.version(), clean_markdown(&release.body, style).trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Releases whose semver is `> from` and `<= to`, newest first. Empty when
+/// `from >= to`.
+fn releases_between<'a>(
+    releases: &'a [GitHubRelease],
+    from: &semver::Version,
+    to: &semver::Version,
+) -> Vec<&'a GitHubRelease> {
+    let mut between: Vec<(semver::Version, &GitHubRelease)> = releases
+        .iter()
+        .filter_map(|release| parse_version(&release.tag_name).map(|version| (version, release)))
+        .filter(|(version, _)| version > from && version <= to)
+        .collect();
+    between.sort_by(|a, b| b.0.cmp(&a.0));
+
+    between.into_iter().map(|(_, release)| release).collect()
+}
+
+/// Per-platform minimum OS versions for `release`, from a `minimums.json`
+/// asset or else a `<!-- minimums -->` block in the body.
+async fn resolve_minimums(
+    ctx: &RouteContext<RequestState>,
+    release: &GitHubRelease,
+) -> Option<BTreeMap<String, String>> {
+    if let Some(minimums) = fetch_text_asset(ctx, release, "minimums.json").await {
+        if let Ok(minimums) = serde_json::from_str::<BTreeMap<String, String>>(&minimums) {
+            return Some(minimums);
+        }
+    }
+
+    let minimums = parse_minimums(&release.body);
+    if minimums.is_empty() {
+        None
+    } else {
+        Some(minimums)
+    }
+}
+
+/// Minimums are written with OS names, so `darwin` is also looked up as `macos`.
+fn minimum_for_target<'a>(minimums: &'a BTreeMap<String, String>, target: &str) -> Option<&'a str> {
+    minimums
+        .get(target)
+        .or_else(|| if target == "darwin" { minimums.get("macos") } else { None })
+        .map(String::as_str)
+}
+
+/// Text assets (changelogs, `minimums.json`) are metadata, not downloads;
+/// anything bigger than this is ignored.
+const MAX_TEXT_ASSET_BYTES: u64 = 256 * 1024;
+
+/// Fetches the release asset named `asset_name` as text. Returns `None` when
+/// the asset is missing, too large or unreadable.
+async fn fetch_text_asset(
+    ctx: &RouteContext<RequestState>,
+    release: &GitHubRelease,
+    asset_name: &str,
+) -> Option<String> {
+    let asset = find_text_asset(release, asset_name)?;
+
+    let resp = asset_request(ctx, reqwest::Method::GET, &asset.browser_download_url)
+        .send()
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let bytes = resp.bytes().await.ok()?;
+
+    decode_text_asset(&bytes)
+}
+
+/// The asset named `asset_name`, unless it's too large to be metadata.
+fn find_text_asset<'a>(release: &'a GitHubRelease, asset_name: &str) -> Option<&'a GitHubAsset> {
+    release
+        .assets
+        .iter()
+        .find(|asset| asset.name == asset_name)
+        .filter(|asset| asset.size <= MAX_TEXT_ASSET_BYTES)
+}
+
+/// A downloaded text asset as UTF-8, re-checking the size GitHub listed.
+fn decode_text_asset(bytes: &[u8]) -> Option<String> {
+    if bytes.len() as u64 > MAX_TEXT_ASSET_BYTES {
+        return None;
+    }
+
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Admin routes require `Authorization: Bearer <ADMIN_TOKEN>`. Without an
+/// `ADMIN_TOKEN` secret they are disabled entirely.
+fn is_admin(req: &worker::Request, ctx: &RouteContext<RequestState>) -> Result<bool> {
+    let token = match ctx.secret("ADMIN_TOKEN") {
+        Ok(token) => token.to_string(),
+        Err(_) => return Ok(false),
+    };
+
+    let provided = req.headers().get("Authorization")?.unwrap_or_default();
+    let expected = format!("Bearer {}", token);
+
+    Ok(!token.is_empty() && constant_time_eq(provided.as_bytes(), expected.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Serializes `value` as the response body in the configured `JSON_CASE`.
+fn json_response<T: serde::Serialize>(ctx: &RouteContext<RequestState>, value: &T) -> Result<Response> {
+    let value = serde_json::to_value(value)?;
+
+    Response::from_json(&ctx.data.json_case.apply(value))
+}
+
+fn env_var(ctx: &RouteContext<RequestState>, name: &str) -> Option<String> {
+    ctx.var(name).ok().map(|var| var.to_string())
+}
+
+fn platform_key(target: &str, arch: &str) -> String {
+    format!("{}-{}", target, arch)
+}
+
+/// Splits a `target-arch` token. Arch names use underscores (`x86_64`), so a
+/// valid token has exactly one hyphen with something on either side.
+fn split_platform(platform: &str) -> Option<(&str, &str)> {
+    let (target, arch) = platform.split_once('-')?;
+
+    if target.is_empty() || arch.is_empty() || arch.contains('-') {
+        return None;
+    }
+
+    Some((target, arch))
+}
+
+/// Picks the asset ending in `extension`. GitHub can list more than one match
+/// (e.g. after a re-upload), so ties go to the highest `id` - the most
+/// recently uploaded asset - and then to the lexically greatest name.
+fn find_asset<'a>(release: &'a GitHubRelease, extension: &str) -> Option<&'a GitHubAsset> {
+    pick_asset(release.assets.iter().filter(|asset| asset.name.ends_with(extension)))
+}
+
+fn pick_asset<'a>(assets: impl Iterator<Item = &'a GitHubAsset>) -> Option<&'a GitHubAsset> {
+    assets.max_by(|a, b| a.id.cmp(&b.id).then_with(|| a.name.cmp(&b.name)))
+}
+
+/// The update bundle (or its signature) for `target`/`arch`. An asset named
+/// for the arch wins; otherwise only assets that don't name any arch are
+/// considered, so an Intel build is never served to Apple Silicon by accident.
+/// Any spelling in `ARCH_MARKERS` names an arch.
+/// A configured `ASSET_PATTERNS` entry for the platform replaces all of this.
+fn find_update_asset<'a>(
+    patterns: &AssetPatterns,
+    release: &'a GitHubRelease,
+    target: &str,
+    arch: &str,
+    signature: bool,
+) -> Option<&'a GitHubAsset> {
+    let (file_extension, sig_file_extension) = get_file_extension(target, arch);
+    let extension = if signature { sig_file_extension } else { file_extension };
+
+    if extension.is_empty() {
+        return None;
+    }
+
+    if let Some(pattern) = patterns.get(target, arch) {
+        return pick_asset(release.assets.iter().filter(|asset| match asset.name.strip_suffix(".sig") {
+            Some(bundle_name) => signature && pattern.is_match(bundle_name),
+            None => !signature && pattern.is_match(&asset.name),
+        }));
+    }
+
+    if let Some(arch_extension) = get_arch_extension(target, arch) {
+        let arch_extension = if signature { format!("{}.sig", arch_extension) } else { arch_extension.to_string() };
+
+        if let Some(asset) = find_asset(release, &arch_extension) {
+            return Some(asset);
+        }
+    }
+
+    let bundles = || release.assets.iter().filter(|asset| asset.name.ends_with(&extension));
+
+    pick_asset(bundles().filter(|asset| named_arch(&asset.name) == Some(arch)))
+        .or_else(|| pick_asset(bundles().filter(|asset| named_arch(&asset.name).is_none())))
+}
+
+/// The arch an asset name spells out, if any. A marker only counts as a whole
+/// token (`_arm64.`, `-x64-`), so a product name like `Flux64` isn't mistaken
+/// for one.
+fn named_arch(name: &str) -> Option<&'static str> {
+    let is_delimiter = |c: Option<char>| c.map_or(true, |c| !c.is_ascii_alphanumeric());
+
+    ARCH_MARKERS.iter().find_map(|&(marker, arch)| {
+        name.match_indices(marker)
+            .any(|(start, _)| {
+                is_delimiter(name[..start].chars().next_back()) && is_delimiter(name[start + marker.len()..].chars().next())
+            })
+            .then_some(arch)
+    })
+}
+
+/// The arch to serve instead when a release has no build for the requested
+/// one, from `FALLBACK_ARCH`: a JSON map of target to arch such as
+/// `{"linux":"x86_64"}`. Unset (or no entry for `target`) means no fallback.
+fn fallback_arch(ctx: &RouteContext<RequestState>, target: &str) -> Option<String> {
+    let fallbacks = env_var(ctx, "FALLBACK_ARCH")?;
+
+    match serde_json::from_str::<BTreeMap<String, String>>(&fallbacks) {
+        Ok(mut fallbacks) => fallbacks.remove(target),
+        Err(err) => {
+            console_error!("[{}] FALLBACK_ARCH is not a JSON map of target to arch: {}", ctx.data.request_id, err);
+            None
+        }
+    }
+}
+
+/// `find_requested_update_asset`, retried with `fallback_arch` when the
+/// release has nothing for `arch`. Also returns the arch that was served.
+fn find_update_asset_or_fallback<'a, 'b>(
+    patterns: &AssetPatterns,
+    release: &'a GitHubRelease,
+    target: &str,
+    arch: &'b str,
+    requested: Option<Installer>,
+    fallback_arch: Option<&'b str>,
+) -> Option<(&'a GitHubAsset, Option<Installer>, &'b str)> {
+    if let Some((asset, installer)) = find_requested_update_asset(patterns, release, target, arch, requested) {
+        return Some((asset, installer, arch));
+    }
+
+    let fallback_arch = fallback_arch.filter(|&fallback_arch| fallback_arch != arch)?;
+    find_requested_update_asset(patterns, release, target, fallback_arch, requested)
+        .map(|(asset, installer)| (asset, installer, fallback_arch))
+}
+
+/// MSI is a Windows installer; asking for it on another target is an error
+/// naming the combination rather than a silent NSIS-less 404.
+fn unsupported_installer(target: &str, installer: Option<Installer>) -> Option<String> {
+    match installer {
+        Some(Installer::Msi) if target != "windows" => Some(format!("installer=msi not supported for target={}", target)),
+        _ => None,
+    }
+}
+
+/// Like `find_update_asset`, but on Windows `requested` picks the installer
+/// (NSIS unless asked otherwise), falling back to the other one when the
+/// release doesn't ship it. Also returns the installer that was found, `None`
+/// off Windows.
+fn find_requested_update_asset<'a>(
+    patterns: &AssetPatterns,
+    release: &'a GitHubRelease,
+    target: &str,
+    arch: &str,
+    requested: Option<Installer>,
+) -> Option<(&'a GitHubAsset, Option<Installer>)> {
+    if target != "windows" {
+        return find_update_asset(patterns, release, target, arch, false).map(|asset| (asset, None));
+    }
+
+    let requested = requested.unwrap_or(Installer::Nsis);
+
+    [requested, requested.other()].into_iter().find_map(|installer| {
+        find_installer_asset(patterns, release, arch, installer, false).map(|asset| (asset, Some(installer)))
+    })
+}
+
+/// The Windows update bundle (or its signature) built with `installer`. MSI
+/// bundles carry a language after the arch (`_x64_en-US.msi.zip`), so the
+/// arch is matched anywhere in the name rather than as a suffix.
+fn find_installer_asset<'a>(
+    patterns: &AssetPatterns,
+    release: &'a GitHubRelease,
+    arch: &str,
+    installer: Installer,
+    signature: bool,
+) -> Option<&'a GitHubAsset> {
+    if installer == Installer::Nsis {
+        return find_update_asset(patterns, release, "windows", arch, signature);
+    }
+
+    let extension = if signature { ".msi.zip.sig" } else { ".msi.zip" };
+    let msi_assets = || release.assets.iter().filter(|asset| asset.name.ends_with(extension));
+
+    pick_asset(msi_assets().filter(|asset| named_arch(&asset.name) == Some(arch)))
+        .or_else(|| pick_asset(msi_assets().filter(|asset| named_arch(&asset.name).is_none())))
+}
+
+/// Download URLs for every other platform in `release`, skipping platforms
+/// without an installer asset.
+fn get_alternate_urls(patterns: &AssetPatterns, release: &GitHubRelease, target: &str, arch: &str) -> BTreeMap<String, String> {
+    PLATFORMS
+        .iter()
+        .filter(|&&(platform_target, platform_arch)| (platform_target, platform_arch) != (target, arch))
+        .filter_map(|&(platform_target, platform_arch)| {
+            find_update_asset(patterns, release, platform_target, platform_arch, false).map(|asset| {
+                (platform_key(platform_target, platform_arch), asset.browser_download_url.clone())
+            })
+        })
+        .collect()
+}
+
+/// Arch-specific update bundle suffixes, as Tauri's bundlers name them.
+fn get_arch_extension(target: &str, arch: &str) -> Option<&'static str> {
+    match (target, arch) {
+        ("darwin", "x86_64") => Some("_x64.app.tar.gz"),
+        ("darwin", "aarch64") => Some("_aarch64.app.tar.gz"),
+        ("linux", "x86_64") => Some("_amd64.AppImage.tar.gz"),
+        ("linux", "aarch64") => Some("_aarch64.AppImage.tar.gz"),
+        ("windows", "x86_64") => Some("_x64-setup.nsis.zip"),
+        ("windows", "aarch64") => Some("_arm64-setup.nsis.zip"),
+        _ => None,
+    }
+}
+
+/// Generic update bundle suffixes, used when a release doesn't name its
+/// bundles by arch.
+fn get_file_extension(target: &str, _arch: &str) -> (String, String) {
+    match target {
+        "darwin" => (".app.tar.gz".to_string(), ".app.tar.gz.sig".to_string()),
+        "linux" => (".AppImage.tar.gz".to_string(), ".AppImage.tar.gz.sig".to_string()),
+        "windows" => (".nsis.zip".to_string(), ".nsis.zip.sig".to_string()),
+        _ => ("".to_string(), "".to_string()),
+    }
+}
+
+/// Fetches a `.sig` asset. The contents are returned byte-for-byte - Tauri v2
+/// signatures span two lines and verification breaks if any whitespace is
+/// touched - unless `SIGNATURE_TRIM` asks for leading/trailing whitespace to be
+/// removed.
+async fn fetch_signature(
+    ctx: &RouteContext<RequestState>,
+    asset: &GitHubAsset,
+) -> std::result::Result<String, &'static str> {
+    let resp = match send_with_retry(ctx, asset_request(ctx, reqwest::Method::GET, &asset.browser_download_url)).await {
+        Ok(resp) => resp,
+        Err(_) => return Err("Failed to fetch signature"),
+    };
+    if is_rate_limited(&resp) {
+        return Err("GitHub rate limit exceeded");
+    }
+    if !resp.status().is_success() {
+        return Err("Failed to fetch signature");
+    }
+
+    let bytes = match resp.bytes().await {
+        Ok(bytes) => bytes,
+        Err(_) => return Err("Failed to read signature"),
+    };
+
+    decode_signature(&bytes).map(|signature| apply_signature_trim(signature, ctx.data.trim_signature))
+}
+
+/// `SIGNATURE_TRIM`: leading/trailing whitespace goes only when asked for.
+fn apply_signature_trim(signature: String, trim: bool) -> String {
+    if trim {
+        signature.trim().to_string()
+    } else {
+        signature
+    }
+}
+
+/// Downloads `asset` and returns its SHA-256 as lowercase hex.
+async fn fetch_sha256(
+    ctx: &RouteContext<RequestState>,
+    asset: &GitHubAsset,
+) -> std::result::Result<String, &'static str> {
+    let resp = match send_with_retry(ctx, asset_request(ctx, reqwest::Method::GET, &asset.browser_download_url)).await {
+        Ok(resp) => resp,
+        Err(_) => return Err("Failed to fetch asset"),
+    };
+    if is_rate_limited(&resp) {
+        return Err("GitHub rate limit exceeded");
+    }
+    if !resp.status().is_success() {
+        return Err("Failed to fetch asset");
+    }
+
+    let bytes = match resp.bytes().await {
+        Ok(bytes) => bytes,
+        Err(_) => return Err("Failed to read asset"),
+    };
+
+    Ok(to_hex(&Sha256::digest(&bytes)))
+}
+
+fn format_signature(signature: String, format: SignatureFormat) -> String {
+    match format {
+        SignatureFormat::Inline => signature,
+        // Tauri `.sig` files are already base64, so they can be wrapped as-is.
+        SignatureFormat::DataUri => format!("data:application/octet-stream;base64,{}", signature.trim()),
+    }
+}
+
+/// Signatures are minisign text files, so anything that isn't non-empty UTF-8
+/// is a broken upload and would only fail later inside the client's verifier.
+fn decode_signature(bytes: &[u8]) -> std::result::Result<String, &'static str> {
+    let signature = match std::str::from_utf8(bytes) {
+        Ok(signature) => signature,
+        Err(_) => return Err("Signature asset is not valid UTF-8"),
+    };
+
+    if signature.trim().is_empty() {
+        return Err("Signature asset is empty");
+    }
+
+    Ok(signature.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    const FIXTURE_RELEASES: &str = r#"[
+        {"tag_name": "v1.1.0", "html_url": "https://github.com/o/r/releases/tag/v1.1.0", "published_at": "2024-02-01T00:00:00Z", "body": "Fixed sync.", "prerelease": false, "draft": false, "author": {"login": "octocat", "avatar_url": "https://avatars.githubusercontent.com/u/583231"}, "assets": []},
+        {"tag_name": "v1.3.0", "html_url": "", "published_at": null, "body": null, "prerelease": false, "draft": true, "author": null, "assets": []},
+        {"tag_name": "v1.2.0-beta.1", "html_url": "https://github.com/o/r/releases/tag/v1.2.0-beta.1", "published_at": "2024-03-01T00:00:00Z", "body": "Try the new UI.", "prerelease": true, "draft": false, "author": null, "assets": []},
+        {"tag_name": "nightly", "html_url": "https://github.com/o/r/releases/tag/nightly", "published_at": "2024-04-01T00:00:00Z", "body": "", "prerelease": false, "draft": false, "author": null, "assets": []},
+        {"tag_name": "v1.1.1", "html_url": "https://github.com/o/r/releases/tag/v1.1.1", "published_at": "2024-02-15T00:00:00Z", "body": "> [!WARNING]\n> YANKED\n\nBroke sync.", "prerelease": false, "draft": false, "author": null, "assets": []},
+        {"tag_name": "v1.0.0", "html_url": "https://github.com/o/r/releases/tag/v1.0.0", "published_at": "2024-01-01T00:00:00Z", "body": "First release.", "prerelease": false, "draft": false, "author": null, "assets": []}
+    ]"#;
+
+    /// Serves releases parsed from a GitHub JSON fixture.
+    struct FixtureSource(&'static str);
+
+    impl ReleaseSource for FixtureSource {
+        async fn releases(&self) -> std::result::Result<Vec<GitHubRelease>, FetchError> {
+            parse_releases(self.0).map_err(|_| FetchError::new("Failed to parse releases"))
+        }
+    }
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Polls a future that never waits, which is all a fixture source does.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut future = std::pin::pin!(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("fixture future should be ready immediately"),
+        }
+    }
+
+    /// Runs an update check against the fixture releases.
+    fn check(query: &QueryOptions, current_version: &str) -> UpdateCheck {
+        check_with_strict(query, current_version, false)
+    }
+
+    fn check_with_strict(query: &QueryOptions, current_version: &str, strict: bool) -> UpdateCheck {
+        let current_version = parse_current_version(current_version).unwrap();
+        block_on(check_for_update(&FixtureSource(FIXTURE_RELEASES), query, &current_version, strict)).unwrap()
+    }
+
+    /// The version an update check would serve, if any.
+    fn served_version(check: &UpdateCheck) -> Option<&str> {
+        match check {
+            UpdateCheck::Available { latest, .. } => Some(latest.version()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn releases_request_asks_for_an_uncompressed_body() {
+        let client = Client::new();
+        let request = releases_request(&client, "https://api.github.com/repos/o/r/releases", "releases-api/0.1.0", None, None)
+            .build()
+            .unwrap();
+
+        assert_eq!(request.headers()["Accept-Encoding"], "identity");
+        assert_eq!(request.headers()["User-Agent"], "releases-api/0.1.0");
+        assert!(!request.headers().contains_key("Authorization"));
+    }
+
+    #[test]
+    fn releases_request_only_authenticates_to_github() {
+        let client = Client::new();
+        let authorization = |url| {
+            releases_request(&client, url, "releases-api/0.1.0", Some("ghp_token"), None)
+                .build()
+                .unwrap()
+                .headers()
+                .get("Authorization")
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+
+        assert_eq!(authorization("https://api.github.com/repos/o/r/releases"), Some("Bearer ghp_token".to_string()));
+        assert_eq!(authorization("http://mirror.example.com/repos/o/r/releases"), None);
+    }
+
+    #[test]
+    fn parse_releases_reads_a_github_body() {
+        let body = r#"[{
+            "url": "https://api.github.com/repos/o/r/releases/1",
+            "id": 1,
+            "tag_name": "v1.1.0",
+            "name": "v1.1.0",
+            "html_url": "https://github.com/o/r/releases/tag/v1.1.0",
+            "published_at": "2024-02-01T00:00:00Z",
+            "body": "Fixed sync.",
+            "prerelease": false,
+            "draft": false,
+            "author": {"login": "octocat", "id": 1, "avatar_url": "https://avatars.githubusercontent.com/u/1"},
+            "assets": [{
+                "id": 10,
+                "name": "app_1.1.0_aarch64.app.tar.gz",
+                "content_type": "application/gzip",
+                "size": 4096,
+                "download_count": 42,
+                "browser_download_url": "https://github.com/o/r/releases/download/v1.1.0/app_1.1.0_aarch64.app.tar.gz"
+            }]
+        }]"#;
+        let releases = parse_releases(body).unwrap();
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].version(), "v1.1.0");
+        assert_eq!(releases[0].assets[0].download_count, 42);
+        assert!(parse_releases("{\"message\": \"Not Found\"}").is_err());
+    }
+
+    #[test]
+    fn parse_releases_drops_drafts() {
+        let releases = block_on(FixtureSource(FIXTURE_RELEASES).releases()).unwrap();
+
+        assert_eq!(releases.len(), 5);
+        assert!(releases.iter().all(|release| release.tag_name != "v1.3.0"));
+    }
+
+    #[test]
+    fn stable_channel_skips_prereleases_and_yanked_releases() {
+        assert_eq!(served_version(&check(&QueryOptions::default(), "1.0.0")), Some("v1.1.0"));
+    }
+
+    #[test]
+    fn beta_channel_includes_prereleases() {
+        let query = QueryOptions { channel: Channel::Beta, ..QueryOptions::default() };
+
+        assert_eq!(served_version(&check(&query, "1.0.0")), Some("v1.2.0-beta.1"));
+    }
+
+    #[test]
+    fn include_yanked_brings_yanked_releases_back() {
+        let query = QueryOptions { include_yanked: true, ..QueryOptions::default() };
+
+        assert_eq!(served_version(&check(&query, "1.0.0")), Some("v1.1.1"));
+    }
+
+    #[test]
+    fn select_latest_ignores_tags_that_are_not_semver() {
+        let check = check(&QueryOptions::default(), "1.0.0");
+
+        match &check {
+            UpdateCheck::Available { releases, latest } => {
+                assert!(releases.iter().any(|release| release.tag_name == "nightly"));
+                assert_ne!(latest.tag_name, "nightly");
+            }
+            _ => panic!("expected an update"),
+        }
+    }
+
+    #[test]
+    fn up_to_date_clients_get_no_update() {
+        assert_eq!(served_version(&check(&QueryOptions::default(), "1.0.0")), Some("v1.1.0"));
+        assert!(matches!(check(&QueryOptions::default(), "1.1.0"), UpdateCheck::UpToDate));
+        assert!(matches!(check(&QueryOptions::default(), "2.0.0"), UpdateCheck::UpToDate));
+    }
+
+    #[test]
+    fn force_serves_the_newest_release_to_clients_already_on_it() {
+        let query = QueryOptions { force: true, ..QueryOptions::default() };
+
+        assert!(matches!(check(&QueryOptions::default(), "1.1.0"), UpdateCheck::UpToDate));
+        assert_eq!(served_version(&check(&query, "1.1.0")), Some("v1.1.0"));
+        assert_eq!(served_version(&check(&query, "2.0.0")), Some("v1.1.0"));
+    }
+
+    #[test]
+    fn strict_mode_rejects_versions_that_were_never_released() {
+        let query = QueryOptions::default();
+
+        assert!(matches!(check_with_strict(&query, "1.0.5", true), UpdateCheck::UnknownVersion));
+        assert_eq!(served_version(&check_with_strict(&query, "1.0.5", false)), Some("v1.1.0"));
+        assert_eq!(served_version(&check_with_strict(&query, "1.0.0", true)), Some("v1.1.0"));
+    }
+
+    #[test]
+    fn strict_mode_knows_yanked_and_prerelease_versions() {
+        let releases = block_on(FixtureSource(FIXTURE_RELEASES).releases()).unwrap();
+
+        assert!(is_released_version(&releases, &semver::Version::new(1, 1, 1)));
+        assert!(is_released_version(&releases, &semver::Version::parse("1.2.0-beta.1").unwrap()));
+        assert!(!is_released_version(&releases, &semver::Version::new(1, 3, 0)));
+        assert!(matches!(check_with_strict(&QueryOptions::default(), "1.1.1", true), UpdateCheck::UpToDate));
+    }
+
+    #[test]
+    fn published_by_comes_from_the_release_author() {
+        let releases = block_on(FixtureSource(FIXTURE_RELEASES).releases()).unwrap();
+        let author = |tag_name| releases.iter().find(|release| release.tag_name == tag_name).unwrap().published_by();
+
+        assert_eq!(author("v1.1.0"), Some("octocat"));
+        assert_eq!(author("v1.0.0"), None);
+
+        match check(&QueryOptions::default(), "1.0.0") {
+            UpdateCheck::Available { latest, .. } => assert_eq!(json!(latest.published_by()), json!("octocat")),
+            _ => panic!("expected an update"),
+        }
+        assert_eq!(json!(author("v1.0.0")), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn every_latest_follows_the_channel() {
+        let latest = |query: &QueryOptions| {
+            let mut releases = block_on(FixtureSource(FIXTURE_RELEASES).releases()).unwrap();
+            select_latest_for(query, &mut releases).map(|release| release.version().to_string())
+        };
+
+        assert_eq!(latest(&QueryOptions::default()), Some("v1.1.0".to_string()));
+        assert_eq!(latest(&QueryOptions { channel: Channel::Beta, ..QueryOptions::default() }), Some("v1.2.0-beta.1".to_string()));
+    }
+
+    #[test]
+    fn no_releases_is_reported() {
+        let current_version = semver::Version::new(1, 0, 0);
+        let check = block_on(check_for_update(&FixtureSource("[]"), &QueryOptions::default(), &current_version, false));
+
+        assert!(matches!(check, Ok(UpdateCheck::NoReleases)));
+    }
+
+    #[test]
+    fn arch_extension_covers_every_platform() {
+        assert_eq!(get_arch_extension("darwin", "x86_64"), Some("_x64.app.tar.gz"));
+        assert_eq!(get_arch_extension("darwin", "aarch64"), Some("_aarch64.app.tar.gz"));
+        assert_eq!(get_arch_extension("linux", "x86_64"), Some("_amd64.AppImage.tar.gz"));
+        assert_eq!(get_arch_extension("linux", "aarch64"), Some("_aarch64.AppImage.tar.gz"));
+        assert_eq!(get_arch_extension("windows", "x86_64"), Some("_x64-setup.nsis.zip"));
+        assert_eq!(get_arch_extension("windows", "aarch64"), Some("_arm64-setup.nsis.zip"));
+    }
+
+    #[test]
+    fn arch_extension_is_none_for_unknown_platforms() {
+        assert_eq!(get_arch_extension("windows", "i686"), None);
+        assert_eq!(get_arch_extension("web", "x86_64"), None);
+        assert_eq!(get_arch_extension("", ""), None);
+    }
+
+    #[test]
+    fn file_extension_covers_every_target() {
+        let extension = |target| get_file_extension(target, "x86_64");
+
+        assert_eq!(extension("darwin"), (".app.tar.gz".to_string(), ".app.tar.gz.sig".to_string()));
+        assert_eq!(extension("linux"), (".AppImage.tar.gz".to_string(), ".AppImage.tar.gz.sig".to_string()));
+        assert_eq!(extension("windows"), (".nsis.zip".to_string(), ".nsis.zip.sig".to_string()));
+    }
+
+    #[test]
+    fn file_extension_is_empty_for_unknown_targets() {
+        assert_eq!(get_file_extension("", ""), (String::new(), String::new()));
+        assert_eq!(get_file_extension("android", "aarch64"), (String::new(), String::new()));
+    }
+
+    #[test]
+    fn parse_version_accepts_tag_prefixes() {
+        let expected = semver::Version::new(1, 2, 3);
+
+        assert_eq!(parse_version("1.2.3"), Some(expected.clone()));
+        assert_eq!(parse_version("v1.2.3"), Some(expected.clone()));
+        assert_eq!(parse_version("refs/tags/v1.2.3"), Some(expected));
+        assert_eq!(parse_version("banana"), None);
+    }
+
+    #[test]
+    fn parse_current_version_rejects_garbage() {
+        assert_eq!(parse_current_version("v0.4.0"), Ok(semver::Version::new(0, 4, 0)));
+        assert!(parse_current_version("banana").unwrap_err().contains("banana"));
+    }
+
+    #[test]
+    fn parse_next_link_finds_the_next_page() {
+        let link = "<https://api.github.com/repos/o/r/releases?page=2>; rel=\"next\", \
+                    <https://api.github.com/repos/o/r/releases?page=5>; rel=\"last\"";
+
+        assert_eq!(parse_next_link(link), Some("https://api.github.com/repos/o/r/releases?page=2".to_string()));
+        assert_eq!(parse_next_link("<https://example.com>; rel=\"prev\""), None);
+    }
+
+    #[test]
+    fn token_only_goes_to_github_over_https() {
+        assert!(is_token_host("https://api.github.com/repos/o/r/releases?page=2", None));
+        assert!(!is_token_host("http://api.github.com/repos/o/r/releases", None));
+        assert!(!is_token_host("https://mirror.example.com/repos/o/r/releases", None));
+        assert!(!is_token_host("https://api.github.com.example.com/releases", None));
+        assert!(!is_token_host("not a url", None));
+    }
+
+    #[test]
+    fn private_asset_downloads_only_send_the_token_to_github() {
+        assert!(is_token_host("https://github.com/o/r/releases/download/v1.1.0/app.tar.gz.sig", None));
+        assert!(!is_token_host("http://github.com/o/r/releases/download/v1.1.0/app.tar.gz.sig", None));
+        assert!(!is_token_host("https://mirror.example.com/o/r/releases/download/v1.1.0/app.tar.gz.sig", None));
+        assert!(!is_token_host("https://github.com.evil.example/app.tar.gz.sig", None));
+        assert!(is_token_host("https://github.corp.example/o/r/releases/download/v1.1.0/app.tar.gz.sig", Some("github.corp.example")));
+    }
+
+    #[test]
+    fn token_goes_to_the_configured_enterprise_host() {
+        assert!(is_token_host("https://github.corp.example/api/v3/repos/o/r/releases", Some("github.corp.example")));
+        assert!(!is_token_host("http://github.corp.example/api/v3/repos/o/r/releases", Some("github.corp.example")));
+        assert!(!is_token_host("https://other.example/api/v3/repos/o/r/releases", Some("github.corp.example")));
+    }
+
+    #[test]
+    fn upstream_errors_name_the_status() {
+        assert_eq!(upstream_error_message(404), "GitHub returned 404 for the releases (check GITHUB_REPO or PRODUCTS)");
+        assert_eq!(upstream_error_message(401), "GitHub returned 401 for the releases (check GITHUB_TOKEN)");
+        assert_eq!(upstream_error_message(451), "GitHub returned 451 for the releases");
+    }
+
+    #[test]
+    fn rate_limit_needs_both_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", "42".parse().unwrap());
+        assert_eq!(github_rate_limit(&headers), None);
+
+        headers.insert("X-RateLimit-Reset", "1700000000".parse().unwrap());
+        assert_eq!(
+            github_rate_limit(&headers),
+            Some(GitHubRateLimit { remaining: "42".to_string(), reset: "1700000000".to_string() })
+        );
+    }
+
+    #[test]
+    fn only_rate_limited_403s_count_as_rate_limits() {
+        assert!(is_rate_limit_status(429, None, false));
+        assert!(is_rate_limit_status(403, Some("0"), false));
+        assert!(is_rate_limit_status(403, Some("12"), true));
+        assert!(!is_rate_limit_status(403, Some("12"), false));
+        assert!(!is_rate_limit_status(403, None, false));
+        assert!(!is_rate_limit_status(404, Some("0"), true));
+    }
+
+    #[test]
+    fn parse_date_accepts_what_github_and_mirrors_send() {
+        let expected = DateTime::parse_from_rfc3339("2024-02-01T12:30:00Z").unwrap();
+
+        assert_eq!(parse_date("2024-02-01T12:30:00Z"), Some(expected));
+        assert_eq!(parse_date("Thu, 01 Feb 2024 12:30:00 +0000"), Some(expected));
+        assert_eq!(parse_date("2024-02-01T12:30:00"), Some(expected));
+        assert_eq!(parse_date("2024-02-01 12:30:00"), Some(expected));
+        assert_eq!(parse_date(" 2024-02-01T12:30:00Z\n"), Some(expected));
+    }
+
+    #[test]
+    fn parse_date_keeps_fractional_seconds_and_offsets() {
+        let date = parse_date("2024-02-01T12:30:00.250").unwrap();
+        assert_eq!(date.timestamp_subsec_millis(), 250);
+
+        let date = parse_date("2024-02-01T14:30:00+02:00").unwrap();
+        assert_eq!(date.with_timezone(&Utc), parse_date("2024-02-01T12:30:00Z").unwrap());
+    }
+
+    #[test]
+    fn parse_date_rejects_garbage() {
+        assert_eq!(parse_date(""), None);
+        assert_eq!(parse_date("yesterday"), None);
+        assert_eq!(parse_date("2024-02-01"), None);
+    }
+
+    #[test]
+    fn redact_tokens_hides_github_tokens() {
+        assert_eq!(redact_tokens("leaked ghp_abcdefghijklmnop1234 here"), "leaked [REDACTED] here");
+        assert_eq!(redact_tokens("github_pat_11ABCDEFG_abcdefghijklmnop"), "[REDACTED]");
+        assert_eq!(redact_tokens("Authorization: Bearer abc.def-ghi_jkl"), "Authorization: [REDACTED]");
+        assert_eq!(redact_tokens("{\"message\": \"Not Found\"}"), "{\"message\": \"Not Found\"}");
+    }
+
+    #[test]
+    fn truncate_to_char_boundary_never_splits_a_character() {
+        assert_eq!(truncate_to_char_boundary("short", 512), "short");
+        assert_eq!(truncate_to_char_boundary("héllo", 2), "h");
+        assert_eq!(truncate_to_char_boundary("héllo", 3), "hé");
+        assert_eq!(truncate_to_char_boundary("日本", 4), "日");
+    }
+
+    #[test]
+    fn trimmed_path_drops_one_trailing_slash() {
+        assert_eq!(trimmed_path("/sizes/"), Some("/sizes"));
+        assert_eq!(trimmed_path("/update/darwin-aarch64/1.0.0/"), Some("/update/darwin-aarch64/1.0.0"));
+        assert_eq!(trimmed_path("/sizes"), None);
+        assert_eq!(trimmed_path("/"), None);
+        assert_eq!(trimmed_path(""), None);
+    }
+
+    #[test]
+    fn trimming_the_path_keeps_query_parameters() {
+        let mut url = Url::parse("https://updates.example.com/sizes/?product=teller&limit=5").unwrap();
+        let path = trimmed_path(url.path()).unwrap().to_string();
+        url.set_path(&path);
+
+        assert_eq!(url.as_str(), "https://updates.example.com/sizes?product=teller&limit=5");
+    }
+
+    #[test]
+    fn invalid_products_config_is_an_error() {
+        assert_eq!(parse_products(None), Ok(BTreeMap::new()));
+        assert!(parse_products(Some("{\"teller\": ")).unwrap_err().starts_with("PRODUCTS is not a JSON map"));
+        assert!(parse_products(Some("[\"teller\"]")).is_err());
+    }
+
+    #[test]
+    fn product_repo_looks_up_configured_products() {
+        let products = parse_products(Some("{\"teller\": \"Valink-Solutions/teller\"}")).unwrap();
+
+        assert_eq!(product_repo(&products, None), Ok(None));
+        assert_eq!(product_repo(&products, Some("teller")), Ok(Some("Valink-Solutions/teller".to_string())));
+        assert_eq!(product_repo(&products, Some("nope")), Err("Unknown product: nope. Configured products: teller".to_string()));
+        assert_eq!(
+            product_repo(&BTreeMap::new(), Some("nope")),
+            Err("Unknown product: nope (no products are configured)".to_string())
+        );
+    }
+
+    #[test]
+    fn request_ids_must_be_short_printable_ascii() {
+        assert!(is_valid_request_id("3f2c9a1e-8b7d-4c6e-9f10-2a3b4c5d6e7f"));
+        assert!(is_valid_request_id("cf-ray:8a1b2c3d"));
+        assert!(is_valid_request_id(&"a".repeat(128)));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id(&"a".repeat(129)));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id("line\nbreak"));
+        assert!(!is_valid_request_id("ünïcode"));
+    }
+
+    #[test]
+    fn client_request_id_is_echoed_unless_invalid() {
+        let generated = || "generated".to_string();
+
+        assert_eq!(select_request_id(Some("cf-ray:8a1b2c3d".to_string()), generated), "cf-ray:8a1b2c3d");
+        assert_eq!(select_request_id(Some("has space".to_string()), generated), "generated");
+        assert_eq!(select_request_id(None, generated), "generated");
+    }
+
+    #[test]
+    fn http_date_is_rfc_7231() {
+        let date = parse_date("1994-11-06T08:49:37Z").unwrap().with_timezone(&Utc);
+
+        assert_eq!(http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn last_modified_follows_asset_reuploads() {
+        let mut signature = asset(2, "app.AppImage.tar.gz.sig", 400);
+        signature.updated_at = "2024-02-03T12:00:00Z".to_string();
+        let release = release_with_assets("v1.1.0", vec![asset(1, "app.AppImage.tar.gz", 4096), signature]);
+
+        assert_eq!(last_modified(&release).map(http_date).as_deref(), Some("Sat, 03 Feb 2024 12:00:00 GMT"));
+    }
+
+    #[test]
+    fn yank_rollback_changes_the_etag_despite_an_older_date() {
+        let served = |query: &QueryOptions| match check(query, "1.0.0") {
+            UpdateCheck::Available { latest, .. } => latest,
+            _ => panic!("expected an update"),
+        };
+        let before = served(&QueryOptions { include_yanked: true, ..QueryOptions::default() });
+        let after = served(&QueryOptions::default());
+        let etag = |release: &GitHubRelease| body_etag(json!({ "version": release.version() }).to_string().as_bytes());
+
+        // Yanking v1.1.1 rolls clients back to the older v1.1.0, so a date
+        // validator would call their cached v1.1.1 payload current.
+        assert!(last_modified(&after) < last_modified(&before));
+        assert!(!etag_matches(&etag(&before), &etag(&after)));
+    }
+
+    #[test]
+    fn signatures_are_only_trimmed_when_asked() {
+        assert_eq!(apply_signature_trim("line1\nline2\n".to_string(), false), "line1\nline2\n");
+        assert_eq!(apply_signature_trim("line1\nline2\n".to_string(), true), "line1\nline2");
+        assert_eq!(apply_signature_trim("  sig \r\n".to_string(), true), "sig");
+    }
+
+    #[test]
+    fn format_signature_wraps_data_uris() {
+        let signature = "dW50cnVzdGVkIGNvbW1lbnQ=\n".to_string();
+
+        assert_eq!(format_signature(signature.clone(), SignatureFormat::Inline), signature);
+        assert_eq!(
+            format_signature(signature, SignatureFormat::DataUri),
+            "data:application/octet-stream;base64,dW50cnVzdGVkIGNvbW1lbnQ="
+        );
+    }
+
+    #[test]
+    fn decode_signature_keeps_valid_signatures_intact() {
+        assert_eq!(decode_signature(b"untrusted comment\nRWQ=\n"), Ok("untrusted comment\nRWQ=\n".to_string()));
+    }
+
+    #[test]
+    fn decode_signature_rejects_broken_uploads() {
+        assert_eq!(decode_signature(&[0xff, 0xfe, 0x00]), Err("Signature asset is not valid UTF-8"));
+        assert_eq!(decode_signature(b""), Err("Signature asset is empty"));
+        assert_eq!(decode_signature(b" \n\t\r\n"), Err("Signature asset is empty"));
+    }
+
+    fn asset(id: u64, name: &str, size: u64) -> GitHubAsset {
+        GitHubAsset {
+            id,
+            name: name.to_string(),
+            size,
+            download_count: 0,
+            browser_download_url: format!("https://github.com/o/r/releases/download/v1.1.0/{}", name),
+            updated_at: String::new(),
+        }
+    }
+
+    fn release_with_assets(tag_name: &str, assets: Vec<GitHubAsset>) -> GitHubRelease {
+        GitHubRelease {
+            tag_name: tag_name.to_string(),
+            html_url: format!("https://github.com/o/r/releases/tag/{}", tag_name),
+            published_at: "2024-02-01T00:00:00Z".to_string(),
+            body: "Notes from the body.".to_string(),
+            prerelease: false,
+            draft: false,
+            author: None,
+            assets,
+        }
+    }
+
+    #[test]
+    fn changelog_asset_is_used_when_present() {
+        let release = release_with_assets(
+            "v1.1.0",
+            vec![asset(1, "app_1.1.0_x64.app.tar.gz", 4096), asset(2, "CHANGELOG.md", 128)],
+        );
+        let changelog = find_text_asset(&release, "CHANGELOG.md").unwrap();
+        let notes = decode_text_asset(b"## 1.1.0\n\n- Fixed **sync**.\n").unwrap();
+
+        assert_eq!(changelog.id, 2);
+        assert_eq!(clean_markdown(&notes, NotesStyle::Default), "- Fixed sync.");
+    }
+
+    #[test]
+    fn changelog_asset_falls_back_when_missing_or_too_large() {
+        let release = release_with_assets("v1.1.0", vec![asset(2, "CHANGELOG.md", MAX_TEXT_ASSET_BYTES + 1)]);
+
+        assert!(find_text_asset(&release, "CHANGELOG.md").is_none());
+        assert!(find_text_asset(&release, "NOTES.md").is_none());
+        assert_eq!(decode_text_asset(&[0xff, 0xfe]), None);
+        assert_eq!(decode_text_asset(&vec![b'a'; MAX_TEXT_ASSET_BYTES as usize + 1]), None);
+    }
+
+    #[test]
+    fn installer_picks_the_windows_bundle() {
+        let patterns = AssetPatterns::default();
+        let release = release_with_assets(
+            "v1.1.0",
+            vec![asset(1, "app_1.1.0_x64-setup.nsis.zip", 4096), asset(2, "app_1.1.0_x64_en-US.msi.zip", 4096)],
+        );
+        let found = |installer| {
+            find_requested_update_asset(&patterns, &release, "windows", "x86_64", installer)
+                .map(|(asset, installer)| (asset.id, installer))
+        };
+
+        assert_eq!(found(None), Some((1, Some(Installer::Nsis))));
+        assert_eq!(found(Some(Installer::Nsis)), Some((1, Some(Installer::Nsis))));
+        assert_eq!(found(Some(Installer::Msi)), Some((2, Some(Installer::Msi))));
+    }
+
+    #[test]
+    fn installer_falls_back_to_the_one_the_release_ships() {
+        let patterns = AssetPatterns::default();
+        let nsis_only = release_with_assets("v1.1.0", vec![asset(1, "app_1.1.0_x64-setup.nsis.zip", 4096)]);
+        let msi_only = release_with_assets("v1.1.0", vec![asset(2, "app_1.1.0_x64_en-US.msi.zip", 4096)]);
+
+        let found = |release, installer| {
+            find_requested_update_asset(&patterns, release, "windows", "x86_64", installer)
+                .map(|(asset, installer)| (asset.id, installer))
+        };
+
+        assert_eq!(found(&nsis_only, Some(Installer::Msi)), Some((1, Some(Installer::Nsis))));
+        assert_eq!(found(&msi_only, None), Some((2, Some(Installer::Msi))));
+    }
+
+    #[test]
+    fn missing_arch_falls_back_to_the_configured_arch() {
+        let patterns = AssetPatterns::default();
+        let release = release_with_assets(
+            "v1.1.0",
+            vec![asset(1, "app_1.1.0_amd64.AppImage.tar.gz", 4096), asset(2, "app_1.1.0_amd64.AppImage.tar.gz.sig", 64)],
+        );
+        let found = |arch, fallback_arch| {
+            find_update_asset_or_fallback(&patterns, &release, "linux", arch, None, fallback_arch)
+                .map(|(asset, installer, served_arch)| (asset.id, installer, served_arch))
+        };
+
+        assert_eq!(found("aarch64", Some("x86_64")), Some((1, None, "x86_64")));
+        assert_eq!(found("x86_64", Some("x86_64")), Some((1, None, "x86_64")));
+        assert_eq!(found("aarch64", None), None);
+        assert_eq!(find_update_asset(&patterns, &release, "linux", "x86_64", true).map(|asset| asset.id), Some(2));
+    }
+
+    #[test]
+    fn channels_resolve_stable_and_beta_from_a_mixed_release_set() {
+        let patterns = AssetPatterns::default();
+        let darwin_assets = |id, version| {
+            vec![
+                asset(id, &format!("app_{}_aarch64.app.tar.gz", version), 4096),
+                asset(id + 1, &format!("app_{}_aarch64.app.tar.gz.sig", version), 64),
+            ]
+        };
+        let mut beta = release_with_assets("v1.2.0-beta.1", darwin_assets(3, "1.2.0-beta.1"));
+        beta.prerelease = true;
+        let releases = vec![release_with_assets("v1.1.0", darwin_assets(1, "1.1.0")), beta];
+
+        let channels = channel_releases(&patterns, &releases, "darwin", "aarch64")
+            .map(|(channel, release)| (channel, release.map(|release| release.version())));
+
+        assert_eq!(channels, [("stable", Some("v1.1.0")), ("beta", Some("v1.2.0-beta.1"))]);
+        assert_eq!(
+            channel_releases(&patterns, &releases, "linux", "x86_64").map(|(_, release)| release.is_none()),
+            [true, true]
+        );
+    }
+
+    #[test]
+    fn msi_is_only_supported_on_windows() {
+        assert_eq!(unsupported_installer("windows", Some(Installer::Msi)), None);
+        assert_eq!(unsupported_installer("linux", None), None);
+        assert_eq!(unsupported_installer("linux", Some(Installer::Nsis)), None);
+        assert_eq!(
+            unsupported_installer("linux", Some(Installer::Msi)),
+            Some("installer=msi not supported for target=linux".to_string())
+        );
+        assert_eq!(
+            unsupported_installer("darwin", Some(Installer::Msi)),
+            Some("installer=msi not supported for target=darwin".to_string())
+        );
+    }
+
+    fn release_with_body(tag_name: &str, body: &str) -> GitHubRelease {
+        GitHubRelease { body: body.to_string(), ..release_with_assets(tag_name, Vec::new()) }
+    }
+
+    #[test]
+    fn releases_between_excludes_from_and_includes_to() {
+        let releases = vec![
+            release_with_body("v1.0.0", "First."),
+            release_with_body("v1.2.0", "Third."),
+            release_with_body("v1.1.0", "Second."),
+        ];
+        let between = |from, to| {
+            releases_between(&releases, &semver::Version::new(1, 0, from), &semver::Version::new(1, to, 0))
+                .iter()
+                .map(|release| release.version())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(between(0, 2), vec!["v1.2.0", "v1.1.0"]);
+        assert_eq!(between(0, 1), vec!["v1.1.0"]);
+        assert!(releases_between(&releases, &semver::Version::new(1, 2, 0), &semver::Version::new(1, 1, 0)).is_empty());
+    }
+
+    #[test]
+    fn collect_notes_since_joins_notes_newest_first_under_headers() {
+        let releases = vec![
+            release_with_body("v1.0.0", "First."),
+            release_with_body("v1.2.0", "## Changes\n\nThird."),
+            release_with_body("v1.1.0", "Second.\n"),
+        ];
+        let notes = collect_notes_since(&releases, &semver::Version::new(1, 0, 0), &releases[1], NotesStyle::Default);
+
+        assert_eq!(notes, "v1.2.0\nThird.\n\nv1.1.0\nSecond.");
+    }
+
+    #[test]
+    fn split_platform_takes_one_target_and_one_arch() {
+        assert_eq!(split_platform("darwin-aarch64"), Some(("darwin", "aarch64")));
+        assert_eq!(split_platform("linux-x86_64"), Some(("linux", "x86_64")));
+        assert_eq!(split_platform("darwin"), None);
+        assert_eq!(split_platform("-x86_64"), None);
+        assert_eq!(split_platform("darwin-"), None);
+        assert_eq!(split_platform("a-b-c"), None);
+    }
+
+    #[test]
+    fn feed_has_authors_and_skips_undated_releases() {
+        let releases = block_on(FixtureSource(FIXTURE_RELEASES).releases()).unwrap();
+        let mut undated = release_with_body("v0.9.0", "No date.");
+        undated.published_at = "soon".to_string();
+        let releases = releases.into_iter().chain(std::iter::once(undated)).collect();
+
+        let feed = atom_feed(&AssetPatterns::default(), NotesStyle::Default, "o/r", "https://example.com/feed.xml", releases, 20);
+
+        assert!(feed.contains("  <author><name>o/r</name></author>\n  <entry>"));
+        assert!(feed.contains("<title>v1.1.0</title>"));
+        assert!(feed.contains("<author><name>octocat</name></author>"));
+        assert!(!feed.contains("v0.9.0"));
+        assert!(!feed.contains("soon"));
+        assert_eq!(feed.matches("<author>").count(), feed.matches("<entry>").count() + 1);
+    }
+
+    #[test]
+    fn selftest_probes_each_platform_of_the_newest_release() {
+        const RELEASES: &str = r#"[
+            {"tag_name": "v1.1.0", "html_url": "", "published_at": "2024-02-01T00:00:00Z", "body": "", "prerelease": false, "draft": false, "author": null, "assets": [
+                {"id": 1, "name": "app_aarch64.app.tar.gz", "size": 4096, "download_count": 0, "browser_download_url": "https://github.com/o/r/releases/download/v1.1.0/app_aarch64.app.tar.gz"},
+                {"id": 2, "name": "app_aarch64.app.tar.gz.sig", "size": 400, "download_count": 0, "browser_download_url": "https://github.com/o/r/releases/download/v1.1.0/app_aarch64.app.tar.gz.sig"}
+            ]}
+        ]"#;
+        let probe = |asset: &GitHubAsset| {
+            let error = asset.name.ends_with(".sig").then_some("HEAD request returned an error status");
+            async move { error }
+        };
+
+        let steps = block_on(selftest_steps(&FixtureSource(RELEASES), &QueryOptions::default(), &AssetPatterns::default(), || 0, probe));
+        let step = |name: &str| steps.iter().find(|step| step["name"] == name).unwrap().clone();
+
+        assert_eq!(steps.len(), 2 + 2 * PLATFORMS.len());
+        assert_eq!(step("fetch_releases")["ok"], true);
+        assert_eq!(step("select_latest")["ok"], true);
+        assert_eq!(step("asset:darwin-aarch64")["ok"], true);
+        assert_eq!(step("signature:darwin-aarch64")["error"], "HEAD request returned an error status");
+        assert_eq!(step("asset:linux-x86_64")["error"], "No matching asset");
+    }
+
+    #[test]
+    fn selftest_stops_after_a_failed_fetch() {
+        let steps = block_on(selftest_steps(
+            &FixtureSource("not json"),
+            &QueryOptions::default(),
+            &AssetPatterns::default(),
+            || 0,
+            |_: &GitHubAsset| async { None },
+        ));
+
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0]["error"], "Failed to parse releases");
+        assert_eq!(steps[1]["error"], "No releases found");
+    }
+
+    #[test]
+    fn web_target_gets_the_hosted_app_url() {
+        assert_eq!(
+            web_update(Some("https://app.example.com".to_string())),
+            Some(json!({ "url": "https://app.example.com" }))
+        );
+        assert_eq!(web_update(None), None);
+    }
+
+    #[test]
+    fn link_header_points_at_the_release_page() {
+        assert_eq!(
+            link_header(&release_with_body("v1.1.0", "")),
+            "<https://github.com/o/r/releases/tag/v1.1.0>; rel=\"alternate\""
+        );
+    }
+
+    #[test]
+    fn named_arch_maps_whole_tokens_only() {
+        assert_eq!(named_arch("app_1.0_x86_64.AppImage.tar.gz"), Some("x86_64"));
+        assert_eq!(named_arch("app_1.0_amd64.AppImage.tar.gz"), Some("x86_64"));
+        assert_eq!(named_arch("App_arm64.app.tar.gz"), Some("aarch64"));
+        assert_eq!(named_arch("App_x64_en-US.msi.zip"), Some("x86_64"));
+        assert_eq!(named_arch("Flux64.app.tar.gz"), None);
+        assert_eq!(named_arch("Relax64-setup.nsis.zip"), None);
+    }
+
+    #[test]
+    fn update_asset_accepts_alternate_arch_spellings() {
+        let patterns = AssetPatterns::default();
+        let linux = release_with_assets(
+            "v1.1.0",
+            vec![asset(1, "app_1.0_x86_64.AppImage.tar.gz", 4096), asset(2, "app_1.0_aarch64.AppImage.tar.gz", 4096)],
+        );
+        let darwin = release_with_assets("v1.1.0", vec![asset(3, "App_arm64.app.tar.gz", 4096)]);
+
+        assert_eq!(
+            find_update_asset(&patterns, &linux, "linux", "x86_64", false).map(|asset| asset.id),
+            Some(1)
+        );
+        assert_eq!(
+            find_update_asset(&patterns, &darwin, "darwin", "aarch64", false).map(|asset| asset.id),
+            Some(3)
+        );
+        assert!(find_update_asset(&patterns, &darwin, "darwin", "x86_64", false).is_none());
+    }
+
+    #[test]
+    fn update_asset_treats_digits_in_the_product_name_as_generic() {
+        let release = release_with_assets("v1.1.0", vec![asset(1, "Flux64.app.tar.gz", 4096)]);
+
+        assert_eq!(
+            find_update_asset(&AssetPatterns::default(), &release, "darwin", "aarch64", false).map(|asset| asset.id),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn pick_asset_prefers_the_newest_upload() {
+        let old = asset(1, "app_1.1.0_x64.app.tar.gz", 4096);
+        let reuploaded = asset(7, "app_1.1.0_x64.app.tar.gz", 4096);
+
+        assert_eq!(pick_asset([&old, &reuploaded].into_iter()).map(|asset| asset.id), Some(7));
+        assert_eq!(pick_asset([&reuploaded, &old].into_iter()).map(|asset| asset.id), Some(7));
+        assert!(pick_asset(std::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn pick_asset_breaks_id_ties_by_name() {
+        let a = asset(3, "app_a.app.tar.gz", 4096);
+        let b = asset(3, "app_b.app.tar.gz", 4096);
+
+        assert_eq!(pick_asset([&b, &a].into_iter()).map(|asset| asset.name.as_str()), Some("app_b.app.tar.gz"));
+        assert_eq!(pick_asset([&a, &b].into_iter()).map(|asset| asset.name.as_str()), Some("app_b.app.tar.gz"));
+    }
+
+    #[test]
+    fn alternate_urls_skip_the_requesting_platform_and_missing_assets() {
+        let release = release_with_assets(
+            "v1.1.0",
+            vec![
+                asset(1, "app_1.1.0_x64.app.tar.gz", 4096),
+                asset(2, "app_1.1.0_aarch64.app.tar.gz", 4096),
+                asset(3, "app_1.1.0_x64-setup.nsis.zip", 4096),
+            ],
+        );
+        let alternates = get_alternate_urls(&AssetPatterns::default(), &release, "darwin", "aarch64");
+
+        assert_eq!(alternates.keys().collect::<Vec<_>>(), vec!["darwin-x86_64", "windows-x86_64"]);
+        assert_eq!(alternates["windows-x86_64"], release.assets[2].browser_download_url);
+    }
+
+    #[test]
+    fn top_assets_are_most_downloaded_first_up_to_the_limit() {
+        let downloaded = |id, name, download_count| GitHubAsset { download_count, ..asset(id, name, 4096) };
+        let releases = vec![
+            release_with_assets("v1.1.0", vec![downloaded(1, "new.dmg", 30), downloaded(2, "new.msi", 5)]),
+            release_with_assets("v1.0.0", vec![downloaded(3, "old.dmg", 120)]),
+        ];
+
+        let order: Vec<(&str, &str)> = assets_by_downloads(&releases)
+            .into_iter()
+            .map(|(release, asset)| (release.tag_name.as_str(), asset.name.as_str()))
+            .collect();
+        assert_eq!(order, vec![("v1.0.0", "old.dmg"), ("v1.1.0", "new.dmg"), ("v1.1.0", "new.msi")]);
+
+        assert_eq!(
+            top_assets(&releases, 2),
+            vec![
+                json!({ "tag_name": "v1.0.0", "asset_name": "old.dmg", "download_count": 120 }),
+                json!({ "tag_name": "v1.1.0", "asset_name": "new.dmg", "download_count": 30 }),
+            ]
+        );
+        assert!(top_assets(&releases, 0).is_empty());
+    }
+
+    #[test]
+    fn platforms_only_lists_platforms_with_an_update_bundle() {
+        let release = release_with_assets(
+            "v1.1.0",
+            vec![
+                asset(1, "app_1.1.0_aarch64.app.tar.gz", 4096),
+                asset(2, "app_1.1.0_aarch64.app.tar.gz.sig", 64),
+                asset(3, "app_1.1.0_x64-setup.exe", 2048),
+                asset(4, "app_1.1.0_arm64-setup.nsis.zip", 2048),
+            ],
+        );
+
+        assert_eq!(platforms_with_assets(&AssetPatterns::default(), &release), vec!["darwin-aarch64", "windows-aarch64"]);
+        assert!(platforms_with_assets(&AssetPatterns::default(), &release_with_assets("v1.1.0", Vec::new())).is_empty());
+    }
+
+    #[test]
+    fn sizes_list_each_covered_platform() {
+        let release = release_with_assets(
+            "v1.1.0",
+            vec![
+                asset(1, "app_1.1.0_aarch64.app.tar.gz", 4096),
+                asset(2, "app_1.1.0_aarch64.app.tar.gz.sig", 64),
+                asset(3, "app_1.1.0_amd64.AppImage.tar.gz", 8192),
+                asset(4, "app_1.1.0_x64-setup.nsis.zip", 2048),
+            ],
+        );
+        let sizes = platform_sizes(&AssetPatterns::default(), &release);
+
+        assert_eq!(
+            sizes.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("darwin-aarch64".to_string(), 4096),
+                ("linux-x86_64".to_string(), 8192),
+                ("windows-x86_64".to_string(), 2048),
+            ]
+        );
+    }
+
+    #[test]
+    fn coverage_flags_platforms_missing_a_signature() {
+        let release = release_with_assets(
+            "v1.1.0",
+            vec![
+                asset(1, "app_1.1.0_aarch64.app.tar.gz", 4096),
+                asset(2, "app_1.1.0_aarch64.app.tar.gz.sig", 64),
+                asset(3, "app_1.1.0_x64.app.tar.gz", 4096),
+            ],
+        );
+        let coverage = platform_coverage(&AssetPatterns::default(), &release);
+
+        assert_eq!(coverage.len(), PLATFORMS.len());
+        assert!(coverage["darwin-aarch64"]);
+        assert!(!coverage["darwin-x86_64"]);
+        assert!(!coverage["linux-x86_64"]);
+    }
+
+    #[test]
+    fn checksum_budget_caps_asset_count_and_size() {
+        let small = asset(1, "app.tar.gz", 1024);
+        let huge = asset(2, "app.dmg", MAX_CHECKSUM_BYTES + 1);
+        let half = asset(3, "app.AppImage", MAX_CHECKSUM_BYTES / 2 + 1);
+
+        assert_eq!(checksum_budget(&[&small]), Ok(()));
+        assert_eq!(checksum_budget(&[&small; MAX_CHECKSUM_ASSETS]), Ok(()));
+        assert!(checksum_budget(&[&small; MAX_CHECKSUM_ASSETS + 1]).unwrap_err().contains("too many assets"));
+        assert_eq!(checksum_budget(&[&huge]), Err("Asset is too large to checksum"));
+        assert!(checksum_budget(&[&half, &half]).unwrap_err().contains("too large to checksum at once"));
+    }
+
+    #[test]
+    fn etag_matches_weak_and_listed_tags() {
+        assert!(etag_matches("W/\"abc\"", "W/\"abc\""));
+        assert!(etag_matches("\"abc\"", "W/\"abc\""));
+        assert!(etag_matches("\"x\", W/\"abc\"", "W/\"abc\""));
+        assert!(etag_matches("*", "W/\"abc\""));
+        assert!(!etag_matches("W/\"abd\"", "W/\"abc\""));
+    }
+}
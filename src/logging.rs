@@ -0,0 +1,22 @@
+/// How much the worker logs, configured with `LOG_LEVEL`. Each level includes
+/// the ones before it; errors are always logged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    /// One line per request. The default.
+    Info,
+    /// Adds every GitHub request and the release each update check picked.
+    Debug,
+}
+
+impl LogLevel {
+    pub fn from_config(value: Option<&str>) -> LogLevel {
+        match value {
+            Some("error") => LogLevel::Error,
+            Some("warn") => LogLevel::Warn,
+            Some("debug") => LogLevel::Debug,
+            _ => LogLevel::Info,
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use std::collections::BTreeMap;
+
+/// Matches the minimums block releases can carry in their body:
+///
+/// ```text
+/// <!-- minimums
+/// windows: 10.0.19041
+/// macos: 11.0
+/// -->
+/// ```
+///
+/// It's an HTML comment so GitHub doesn't render it.
+const MINIMUMS_BLOCK: &str = r"(?s)<!--\s*minimums\s*\n(.*?)-->\n?";
+
+/// How much of GitHub's generated release-note boilerplate to keep,
+/// configured with `NOTES_STYLE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotesStyle {
+    /// Notes are cleaned as-is.
+    Default,
+    /// Drops the `**Full Changelog**: ...` line and turns the
+    /// `## What's Changed` header into a plain line instead of removing it.
+    GitHub,
+}
+
+impl NotesStyle {
+    pub fn from_config(value: Option<&str>) -> NotesStyle {
+        match value {
+            Some("github") => NotesStyle::GitHub,
+            _ => NotesStyle::Default,
+        }
+    }
+}
+
+/// Turns release-note markdown into plain text for the updater dialog.
+///
+/// Headings are dropped, as are paragraphs made up entirely of bold/italic
+/// text - that's how bundlers write boilerplate like tauri-action's
+/// `**_See the assets to download and install this version._**`. Everything
+/// else keeps its text: links become their label, lists keep their markers,
+/// code blocks are kept verbatim and table rows are joined with ` | `.
+pub fn clean_markdown(markdown: &str, style: NotesStyle) -> String {
+    let markdown = match style {
+        NotesStyle::Default => markdown.to_string(),
+        NotesStyle::GitHub => normalize_generated_notes(markdown),
+    };
+
+    let mut writer = PlainTextWriter::default();
+    for event in Parser::new_ext(&markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS) {
+        writer.event(event);
+    }
+    writer.finish()
+}
+
+#[derive(Default)]
+struct PlainTextWriter {
+    output: String,
+    /// The paragraph being written, held back until we know it isn't
+    /// emphasis-only boilerplate.
+    paragraph: Option<String>,
+    paragraph_has_plain_text: bool,
+    /// One entry per open list: the next number for ordered lists.
+    lists: Vec<Option<u64>>,
+    emphasis_depth: usize,
+    skip_depth: usize,
+    table_cell: usize,
+}
+
+impl PlainTextWriter {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::Heading(..)) | Event::Start(Tag::Image(..)) => self.skip_depth += 1,
+            Event::End(Tag::Heading(..)) | Event::End(Tag::Image(..)) => self.skip_depth -= 1,
+            _ if self.skip_depth > 0 => {}
+
+            Event::Start(Tag::Paragraph) => {
+                self.paragraph = Some(String::new());
+                self.paragraph_has_plain_text = false;
+            }
+            Event::End(Tag::Paragraph) => {
+                if let Some(paragraph) = self.paragraph.take() {
+                    if self.paragraph_has_plain_text {
+                        self.output.push_str(paragraph.trim_end());
+                        self.end_block();
+                    }
+                }
+            }
+            Event::Start(Tag::Emphasis) | Event::Start(Tag::Strong) => self.emphasis_depth += 1,
+            Event::End(Tag::Emphasis) | Event::End(Tag::Strong) => self.emphasis_depth -= 1,
+
+            Event::Start(Tag::List(start)) => {
+                self.start_line();
+                self.lists.push(start);
+            }
+            Event::End(Tag::List(_)) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.end_block();
+                }
+            }
+            Event::Start(Tag::Item) => {
+                self.start_line();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.output.push_str(&indent);
+                self.output.push_str(&marker);
+            }
+            Event::End(Tag::Item) => self.start_line(),
+
+            Event::Start(Tag::CodeBlock(_)) | Event::Start(Tag::BlockQuote) => self.start_line(),
+            Event::End(Tag::CodeBlock(_)) | Event::End(Tag::BlockQuote) => self.end_block(),
+
+            Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow) => self.table_cell = 0,
+            Event::Start(Tag::TableCell) => {
+                if self.table_cell > 0 {
+                    self.push(" | ", false);
+                }
+                self.table_cell += 1;
+            }
+            Event::End(Tag::TableHead) | Event::End(Tag::TableRow) => self.start_line(),
+            Event::End(Tag::Table(_)) => self.end_block(),
+
+            Event::Text(text) | Event::Code(text) => {
+                let plain = self.emphasis_depth == 0 && !text.trim().is_empty();
+                self.push(&text, plain);
+            }
+            Event::SoftBreak | Event::HardBreak => self.push("\n", false),
+            Event::TaskListMarker(checked) => self.push(if checked { "[x] " } else { "[ ] " }, false),
+
+            // Raw HTML (including the minimums block) and rules have no
+            // plain-text form.
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, text: &str, plain: bool) {
+        match self.paragraph.as_mut() {
+            Some(paragraph) => {
+                paragraph.push_str(text);
+                self.paragraph_has_plain_text |= plain;
+            }
+            None => self.output.push_str(text),
+        }
+    }
+
+    fn start_line(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+    }
+
+    fn end_block(&mut self) {
+        self.start_line();
+        if self.lists.is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+
+    fn finish(self) -> String {
+        self.output.trim().to_string()
+    }
+}
+
+/// Parses the minimums block into `os -> version`. Returns an empty map when
+/// the body has no block.
+pub fn parse_minimums(markdown: &str) -> BTreeMap<String, String> {
+    let minimums_re = regex::Regex::new(MINIMUMS_BLOCK).unwrap();
+
+    let block = match minimums_re.captures(markdown) {
+        Some(captures) => captures.get(1).map_or("", |block| block.as_str()),
+        None => return BTreeMap::new(),
+    };
+
+    block
+        .lines()
+        .filter_map(|line| {
+            let (os, version) = line.split_once(':')?;
+            let (os, version) = (os.trim(), version.trim());
+            (!os.is_empty() && !version.is_empty()).then(|| (os.to_ascii_lowercase(), version.to_string()))
+        })
+        .collect()
+}
+
+fn normalize_generated_notes(markdown: &str) -> String {
+    let full_changelog_re = regex::Regex::new(r"(?m)^\*\*Full Changelog\*\*:.*\n?").unwrap();
+    let whats_changed_re = regex::Regex::new(r"(?m)^#+\s*What's Changed\s*$").unwrap();
+
+    let no_changelog = full_changelog_re.replace_all(markdown, "");
+    whats_changed_re.replace_all(&no_changelog, "What's Changed").to_string()
+}
+
+/// Renders release notes to HTML for clients that display them directly.
+///
+/// Raw HTML in the markdown is dropped and links or images pointing at
+/// anything other than http(s)/mailto lose their destination, so the output
+/// only ever contains the tags pulldown-cmark itself emits.
+pub fn render_notes_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH)
+        .filter(|event| !matches!(event, Event::Html(_)))
+        .map(|event| match event {
+            Event::Start(Tag::Link(kind, dest, title)) => Event::Start(Tag::Link(kind, safe_url(dest), title)),
+            Event::Start(Tag::Image(kind, dest, title)) => Event::Start(Tag::Image(kind, safe_url(dest), title)),
+            event => event,
+        });
+
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+    output
+}
+
+fn safe_url(dest: CowStr) -> CowStr {
+    let lowered = dest.trim().to_ascii_lowercase();
+    let has_scheme = lowered.split(['/', '?', '#']).next().unwrap_or("").contains(':');
+
+    if !has_scheme || ["http:", "https:", "mailto:"].iter().any(|scheme| lowered.starts_with(scheme)) {
+        dest
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(markdown: &str) -> String {
+        clean_markdown(markdown, NotesStyle::Default)
+    }
+
+    #[test]
+    fn drops_headers() {
+        assert_eq!(clean("## v1.2.0\nFixed sync.\n"), "Fixed sync.");
+        assert_eq!(clean("# Title\n\n### Details\n\nBody"), "Body");
+    }
+
+    #[test]
+    fn keeps_the_text_of_inline_emphasis() {
+        assert_eq!(clean("Fixed a **big** bug in _sync_."), "Fixed a big bug in sync.");
+    }
+
+    #[test]
+    fn drops_paragraphs_that_are_only_emphasis() {
+        assert_eq!(clean("**Heads up**\n\nBody"), "Body");
+        assert_eq!(clean("_Just italics_"), "");
+    }
+
+    #[test]
+    fn keeps_link_labels() {
+        assert_eq!(clean("See [the docs](https://example.com) for more."), "See the docs for more.");
+    }
+
+    #[test]
+    fn strips_the_tauri_action_boilerplate() {
+        let body = "**_See the assets to download and install this version._**\n\nFixed the updater.";
+
+        assert_eq!(clean(body), "Fixed the updater.");
+    }
+
+    #[test]
+    fn keeps_lists_code_and_tables() {
+        let body = "- one\n- two\n  1. a\n  2. b\n\n```\nlet x = 1;\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n";
+
+        assert_eq!(clean(body), "- one\n- two\n  1. a\n  2. b\n\nlet x = 1;\n\na | b\n1 | 2");
+    }
+
+    #[test]
+    fn drops_the_minimums_block() {
+        let body = "<!-- minimums\nwindows: 10.0.19041\n-->\nFixed sync.";
+
+        assert_eq!(clean(body), "Fixed sync.");
+    }
+
+    #[test]
+    fn github_style_keeps_whats_changed() {
+        let body = "## What's Changed\n* Fix sync by @a in #1\n\n**Full Changelog**: https://github.com/o/r/compare/v1...v2\n";
+
+        assert_eq!(clean_markdown(body, NotesStyle::GitHub), "What's Changed\n\n- Fix sync by @a in #1");
+    }
+
+    #[test]
+    fn parses_minimums() {
+        let minimums = parse_minimums("Notes\n<!-- minimums\nWindows: 10.0.19041\nmacos: 11.0\n-->\n");
+
+        assert_eq!(minimums.get("windows").map(String::as_str), Some("10.0.19041"));
+        assert_eq!(minimums.get("macos").map(String::as_str), Some("11.0"));
+        assert!(parse_minimums("No block").is_empty());
+    }
+
+    #[test]
+    fn html_drops_raw_html_and_unsafe_links() {
+        let html = render_notes_html("<script>x</script>\n\n[a](javascript:alert(1)) [b](https://example.com)");
+
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("href=\"https://example.com\""));
+    }
+}
@@ -0,0 +1,35 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// Asset-name patterns that replace the built-in suffixes, configured with
+/// `ASSET_PATTERNS` as a JSON map from `target-arch` (or just `target`) to a
+/// regex. A platform's signature is the asset that matches once its `.sig` is
+/// stripped.
+#[derive(Debug, Default)]
+pub struct AssetPatterns(BTreeMap<String, Regex>);
+
+impl AssetPatterns {
+    pub fn from_config(value: Option<&str>) -> Result<AssetPatterns, String> {
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(AssetPatterns::default()),
+        };
+
+        let patterns: BTreeMap<String, String> = serde_json::from_str(value)
+            .map_err(|err| format!("ASSET_PATTERNS is not a JSON map of platform to regex: {}", err))?;
+
+        patterns
+            .into_iter()
+            .map(|(platform, pattern)| match Regex::new(&pattern) {
+                Ok(regex) => Ok((platform, regex)),
+                Err(err) => Err(format!("ASSET_PATTERNS has an invalid pattern for {}: {}", platform, err)),
+            })
+            .collect::<Result<_, _>>()
+            .map(AssetPatterns)
+    }
+
+    /// The pattern for `target`/`arch`, preferring one keyed by both.
+    pub fn get(&self, target: &str, arch: &str) -> Option<&Regex> {
+        self.0.get(&format!("{}-{}", target, arch)).or_else(|| self.0.get(target))
+    }
+}
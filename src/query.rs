@@ -0,0 +1,225 @@
+use worker::Url;
+
+/// Which releases an update check may offer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Skips GitHub prereleases.
+    Stable,
+    /// Includes prereleases.
+    Beta,
+}
+
+/// How release notes are returned alongside the plain-text `notes`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotesFormat {
+    Clean,
+    Html,
+}
+
+/// Which Windows installer's update bundle to serve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Installer {
+    Nsis,
+    Msi,
+}
+
+impl Installer {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Installer::Nsis => "nsis",
+            Installer::Msi => "msi",
+        }
+    }
+
+    pub fn other(self) -> Installer {
+        match self {
+            Installer::Nsis => Installer::Msi,
+            Installer::Msi => Installer::Nsis,
+        }
+    }
+}
+
+/// How the `signature` field is encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureFormat {
+    Inline,
+    DataUri,
+}
+
+/// Every query parameter the worker understands, parsed once per request.
+/// Parameters a route doesn't use are simply ignored by it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryOptions {
+    pub arch: Option<String>,
+    pub asset: Option<String>,
+    pub channel: Channel,
+    pub force: bool,
+    pub include_prereleases: bool,
+    pub include_yanked: bool,
+    /// `None` unless `?installer=` was given; Windows then serves NSIS.
+    pub installer: Option<Installer>,
+    pub limit: Option<usize>,
+    pub notes: NotesFormat,
+    pub notes_since: Option<semver::Version>,
+    pub platforms_only: bool,
+    pub product: Option<String>,
+    pub sig: SignatureFormat,
+    pub tag: Option<String>,
+    pub target: Option<String>,
+    pub verbose_error: bool,
+    pub with_alternates: bool,
+    pub with_author: bool,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            arch: None,
+            asset: None,
+            channel: Channel::Stable,
+            force: false,
+            include_prereleases: false,
+            include_yanked: false,
+            installer: None,
+            limit: None,
+            notes: NotesFormat::Clean,
+            notes_since: None,
+            platforms_only: false,
+            product: None,
+            sig: SignatureFormat::Inline,
+            tag: None,
+            target: None,
+            verbose_error: false,
+            with_alternates: false,
+            with_author: false,
+        }
+    }
+}
+
+impl QueryOptions {
+    /// Parses the query string of `url`. Unknown parameters are ignored, but a
+    /// known parameter with a bad value is an error naming it.
+    pub fn from_url(url: &Url) -> Result<QueryOptions, String> {
+        let mut options = QueryOptions::default();
+
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "arch" => options.arch = Some(value.into_owned()),
+                "asset" => options.asset = Some(value.into_owned()),
+                "channel" => {
+                    options.channel = match value.as_ref() {
+                        "stable" => Channel::Stable,
+                        "beta" => Channel::Beta,
+                        _ => return Err(format!("Invalid channel: {}", value)),
+                    }
+                }
+                "force" => options.force = parse_bool(&key, &value)?,
+                "include_prereleases" => options.include_prereleases = parse_bool(&key, &value)?,
+                "include_yanked" => options.include_yanked = parse_bool(&key, &value)?,
+                "installer" => {
+                    options.installer = match value.as_ref() {
+                        "nsis" => Some(Installer::Nsis),
+                        "msi" => Some(Installer::Msi),
+                        _ => return Err(format!("Invalid installer: {}", value)),
+                    }
+                }
+                "limit" => match value.parse::<usize>() {
+                    Ok(limit) => options.limit = Some(limit),
+                    Err(_) => return Err(format!("Invalid limit: {}", value)),
+                },
+                "notes" => {
+                    options.notes = match value.as_ref() {
+                        "clean" => NotesFormat::Clean,
+                        "html" => NotesFormat::Html,
+                        _ => return Err(format!("Invalid notes: {}", value)),
+                    }
+                }
+                "notes_since" => match crate::parse_version(&value) {
+                    Some(version) => options.notes_since = Some(version),
+                    None => return Err(format!("Invalid notes_since version: {}", value)),
+                },
+                "platforms_only" => options.platforms_only = parse_bool(&key, &value)?,
+                "product" => options.product = Some(value.into_owned()),
+                "sig" => {
+                    options.sig = match value.as_ref() {
+                        "inline" => SignatureFormat::Inline,
+                        "datauri" => SignatureFormat::DataUri,
+                        _ => return Err(format!("Invalid sig: {}", value)),
+                    }
+                }
+                "tag" => options.tag = Some(value.into_owned()),
+                "target" => options.target = Some(value.into_owned()),
+                "verbose_error" => options.verbose_error = parse_bool(&key, &value)?,
+                "with_alternates" => options.with_alternates = parse_bool(&key, &value)?,
+                "with_author" => options.with_author = parse_bool(&key, &value)?,
+                _ => {}
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("Invalid {}: expected true or false", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Result<QueryOptions, String> {
+        QueryOptions::from_url(&Url::parse(&format!("https://updates.example.com/latest?{}", query)).unwrap())
+    }
+
+    #[test]
+    fn no_parameters_gives_the_defaults() {
+        assert_eq!(parse(""), Ok(QueryOptions::default()));
+    }
+
+    #[test]
+    fn unknown_parameters_are_ignored() {
+        assert_eq!(parse("utm_source=app&cache_bust=123"), Ok(QueryOptions::default()));
+    }
+
+    #[test]
+    fn bools_only_accept_true_or_false() {
+        assert_eq!(parse("force=true").map(|options| options.force), Ok(true));
+        assert_eq!(parse("force=false").map(|options| options.force), Ok(false));
+        assert_eq!(parse("force=yes"), Err("Invalid force: expected true or false".to_string()));
+        assert_eq!(parse("with_author=1"), Err("Invalid with_author: expected true or false".to_string()));
+    }
+
+    #[test]
+    fn enums_parse_their_values() {
+        assert_eq!(parse("channel=beta").map(|options| options.channel), Ok(Channel::Beta));
+        assert_eq!(parse("installer=msi").map(|options| options.installer), Ok(Some(Installer::Msi)));
+        assert_eq!(parse("sig=datauri").map(|options| options.sig), Ok(SignatureFormat::DataUri));
+        assert_eq!(parse("notes=html").map(|options| options.notes), Ok(NotesFormat::Html));
+    }
+
+    #[test]
+    fn enums_reject_unknown_values() {
+        assert_eq!(parse("channel=nightly"), Err("Invalid channel: nightly".to_string()));
+        assert_eq!(parse("installer=exe"), Err("Invalid installer: exe".to_string()));
+        assert_eq!(parse("sig=base64"), Err("Invalid sig: base64".to_string()));
+        assert_eq!(parse("notes=markdown"), Err("Invalid notes: markdown".to_string()));
+    }
+
+    #[test]
+    fn limit_must_be_a_number() {
+        assert_eq!(parse("limit=5").map(|options| options.limit), Ok(Some(5)));
+        assert_eq!(parse("limit=-1"), Err("Invalid limit: -1".to_string()));
+        assert_eq!(parse("limit=ten"), Err("Invalid limit: ten".to_string()));
+    }
+
+    #[test]
+    fn notes_since_must_be_semver() {
+        assert_eq!(parse("notes_since=v1.2.0").map(|options| options.notes_since), Ok(Some(semver::Version::new(1, 2, 0))));
+        assert_eq!(parse("notes_since=1.2"), Err("Invalid notes_since version: 1.2".to_string()));
+    }
+}